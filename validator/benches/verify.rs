@@ -0,0 +1,89 @@
+// Run with `cargo bench -p validator --features bench`.
+
+use criterion::{Criterion, criterion_group, criterion_main};
+use jsonwebtoken::jwk::{JwkSet, KeyAlgorithm};
+use jsonwebtoken::{Algorithm, EncodingKey, Header, encode};
+use std::hint::black_box;
+use validator::{Claims, verify_jwt};
+
+#[path = "../tests/common/mod.rs"]
+mod common;
+use common::{
+    EC_PEM, ED25519_PEM, RSA_PEM, create_test_ec_jwk, create_test_ed25519_jwk, create_test_key_jwk,
+};
+
+// A provider-shaped set: one key per algorithm, so every lookup scans past the others
+fn bench_jwks() -> JwkSet {
+    let mut ed25519 = create_test_ed25519_jwk("ed25519");
+    ed25519.common.key_algorithm = Some(KeyAlgorithm::EdDSA);
+    JwkSet {
+        keys: vec![
+            create_test_key_jwk("rsa"),
+            create_test_ec_jwk("ec"),
+            ed25519,
+        ],
+    }
+}
+
+fn bench_token(kid: &str, alg: Algorithm, key: &EncodingKey) -> String {
+    let mut header = Header::new(alg);
+    header.kid = Some(kid.to_string());
+    let claims = Claims {
+        aud: vec!["test_audience".to_string()],
+        company: "company".to_string(),
+        sub: "user123".to_string(),
+        exp: Some(u64::MAX),
+        iat: None,
+        iss: "test_issuer".to_string(),
+        at_hash: None,
+        c_hash: None,
+        nonce: None,
+    };
+    encode(&header, &claims, key).unwrap()
+}
+
+fn verify(c: &mut Criterion) {
+    let jwks = bench_jwks();
+    let cases = [
+        (
+            "RS256",
+            bench_token(
+                "rsa",
+                Algorithm::RS256,
+                &EncodingKey::from_rsa_pem(RSA_PEM.as_bytes()).unwrap(),
+            ),
+        ),
+        (
+            "ES256",
+            bench_token(
+                "ec",
+                Algorithm::ES256,
+                &EncodingKey::from_ec_pem(EC_PEM.as_bytes()).unwrap(),
+            ),
+        ),
+        (
+            "EdDSA",
+            bench_token(
+                "ed25519",
+                Algorithm::EdDSA,
+                &EncodingKey::from_ed_pem(ED25519_PEM.as_bytes()).unwrap(),
+            ),
+        ),
+    ];
+
+    let mut group = c.benchmark_group("verify_jwt");
+    for (name, token) in &cases {
+        assert_eq!(
+            verify_jwt(token, &jwks),
+            Ok(true),
+            "{name} fixture must verify"
+        );
+        group.bench_function(*name, |b| {
+            b.iter(|| verify_jwt(black_box(token), black_box(&jwks)))
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, verify);
+criterion_main!(benches);
@@ -0,0 +1,181 @@
+//! Fetching JWKS over HTTPS for off-chain verification.
+
+use std::collections::HashMap;
+use std::io::Read;
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+use jsonwebtoken::jwk::JwkSet;
+
+use crate::{ErrorInJwt, load_jwks, verify_jwt};
+
+/// Largest JWKS document accepted from a remote server.
+pub const MAX_JWKS_BYTES: u64 = 64 * 1024;
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+const READ_TIMEOUT: Duration = Duration::from_secs(10);
+
+struct CachedJwks {
+    jwks: JwkSet,
+    expires_at: Instant,
+}
+
+// Everything a fetch needs, cheap to clone so it can run without holding the cache
+#[derive(Clone)]
+struct Fetcher {
+    agent: ureq::Agent,
+    allow_http: bool,
+}
+
+impl Default for Fetcher {
+    fn default() -> Self {
+        Self {
+            agent: ureq::AgentBuilder::new()
+                .timeout_connect(CONNECT_TIMEOUT)
+                .timeout_read(READ_TIMEOUT)
+                .build(),
+            allow_http: false,
+        }
+    }
+}
+
+impl Fetcher {
+    fn fetch(&self, jwks_url: &str) -> Result<(JwkSet, Option<Duration>), ErrorInJwt> {
+        let allowed = jwks_url.starts_with("https://")
+            || (self.allow_http && jwks_url.starts_with("http://"));
+        if !allowed {
+            return Err(ErrorInJwt::InsecureJwksUrl);
+        }
+
+        let response = self
+            .agent
+            .get(jwks_url)
+            .call()
+            .map_err(|_| ErrorInJwt::JwksFetchFailed)?;
+        let max_age = response.header("Cache-Control").and_then(parse_max_age);
+        let mut body = Vec::new();
+        response
+            .into_reader()
+            .take(MAX_JWKS_BYTES + 1)
+            .read_to_end(&mut body)
+            .map_err(|_| ErrorInJwt::JwksFetchFailed)?;
+        if body.len() as u64 > MAX_JWKS_BYTES {
+            return Err(ErrorInJwt::JwksFetchFailed);
+        }
+        let jwks = load_jwks(&body)?;
+        Ok((jwks, max_age))
+    }
+}
+
+/// JWKS fetched by URL, kept for as long as the response's `Cache-Control: max-age` allows.
+#[derive(Default)]
+pub struct JwksCache {
+    entries: HashMap<String, CachedJwks>,
+    fetcher: Fetcher,
+}
+
+impl JwksCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Also accept plain `http://` URLs. Only meant for local development and tests.
+    pub fn allow_http(mut self) -> Self {
+        self.fetcher.allow_http = true;
+        self
+    }
+
+    /// Returns the cached JWKS for `jwks_url`, fetching it when missing or expired.
+    pub fn get_jwks(&mut self, jwks_url: &str) -> Result<JwkSet, ErrorInJwt> {
+        get_jwks_through(self, jwks_url)
+    }
+
+    /// Verifies `token` against the JWKS at `jwks_url`, going through the cache.
+    pub fn verify_with_jwks_url(
+        &mut self,
+        token: &str,
+        jwks_url: &str,
+    ) -> Result<bool, ErrorInJwt> {
+        let jwks = self.get_jwks(jwks_url)?;
+        verify_jwt(token, &jwks)
+    }
+
+    fn cached(&self, jwks_url: &str) -> Option<JwkSet> {
+        self.entries
+            .get(jwks_url)
+            .filter(|cached| Instant::now() < cached.expires_at)
+            .map(|cached| cached.jwks.clone())
+    }
+
+    // Expired entries are dropped on every insert, so URLs that stop being used don't pile up
+    fn store(&mut self, jwks_url: &str, jwks: JwkSet, max_age: Option<Duration>) {
+        let now = Instant::now();
+        self.entries.retain(|_, cached| now < cached.expires_at);
+        match max_age {
+            Some(max_age) if !max_age.is_zero() => {
+                self.entries.insert(
+                    jwks_url.into(),
+                    CachedJwks {
+                        jwks,
+                        expires_at: now + max_age,
+                    },
+                );
+            }
+            _ => {
+                self.entries.remove(jwks_url);
+            }
+        }
+    }
+}
+
+// How `get_jwks_through` reaches a cache: directly, or through the lock of a shared one
+trait CacheAccess {
+    fn with<T>(&mut self, f: impl FnOnce(&mut JwksCache) -> T) -> Result<T, ErrorInJwt>;
+}
+
+impl CacheAccess for &mut JwksCache {
+    fn with<T>(&mut self, f: impl FnOnce(&mut JwksCache) -> T) -> Result<T, ErrorInJwt> {
+        Ok(f(self))
+    }
+}
+
+impl CacheAccess for &Mutex<JwksCache> {
+    fn with<T>(&mut self, f: impl FnOnce(&mut JwksCache) -> T) -> Result<T, ErrorInJwt> {
+        let mut cache = self.lock().map_err(|_| ErrorInJwt::JwksFetchFailed)?;
+        Ok(f(&mut cache))
+    }
+}
+
+// The cache is only accessed to read or update an entry, never across the request itself
+fn get_jwks_through(mut cache: impl CacheAccess, jwks_url: &str) -> Result<JwkSet, ErrorInJwt> {
+    let (cached, fetcher) = cache.with(|cache| (cache.cached(jwks_url), cache.fetcher.clone()))?;
+    if let Some(jwks) = cached {
+        return Ok(jwks);
+    }
+    let (jwks, max_age) = fetcher.fetch(jwks_url)?;
+    cache.with(|cache| cache.store(jwks_url, jwks.clone(), max_age))?;
+    Ok(jwks)
+}
+
+// `no-store`/`no-cache` win over any `max-age` in the same header
+fn parse_max_age(cache_control: &str) -> Option<Duration> {
+    let mut max_age = None;
+    for directive in cache_control.split(',').map(str::trim) {
+        let directive = directive.to_ascii_lowercase();
+        if directive == "no-store" || directive == "no-cache" {
+            return None;
+        }
+        if let Some(seconds) = directive.strip_prefix("max-age=") {
+            max_age = seconds.parse().ok().map(Duration::from_secs);
+        }
+    }
+    max_age
+}
+
+/// Fetches the JWKS at `jwks_url` (HTTPS only) and verifies `token` against it, using a
+/// process-wide cache.
+pub fn verify_with_jwks_url(token: &str, jwks_url: &str) -> Result<bool, ErrorInJwt> {
+    static CACHE: OnceLock<Mutex<JwksCache>> = OnceLock::new();
+    let jwks = get_jwks_through(CACHE.get_or_init(Default::default), jwks_url)?;
+    verify_jwt(token, &jwks)
+}
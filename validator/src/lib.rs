@@ -1,25 +1,96 @@
-use jsonwebtoken::crypto::verify;
-use jsonwebtoken::jwk::{AlgorithmParameters, Jwk, JwkSet};
-// use jsonwebtoken::{Algorithm, DecodingKey, Header, TokenData, Validation, decode, decode_header};
-use jsonwebtoken::{DecodingKey, Header, TokenData, decode_header};
-use serde::{Deserialize, Serialize};
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use jsonwebtoken::errors::ErrorKind;
+use jsonwebtoken::jwk::{AlgorithmParameters, EllipticCurve, Jwk, JwkSet, KeyAlgorithm};
+use jsonwebtoken::{Algorithm, DecodingKey, Header, TokenData, Validation, decode, decode_header};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Deserializer, Serialize};
+use sha2::{Digest, Sha256, Sha384, Sha512};
 
+#[cfg(feature = "http")]
+pub mod fetch;
+#[cfg(feature = "substrate")]
+pub mod substrate;
+
+/// Errors are numbered with stable codes (see `ErrorInJwt::code`), so new variants
+/// must take the next free value and existing ones must never be renumbered.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
 pub enum ErrorInJwt {
-    InvalidJwt,
-    InvalidJwks,
-    InvalidJwk,
-    InvalidJson,
-    InvalidToken,
-    TokenExpired,
-    AlgorithmNotSupported,
-    NoIssuer,
-    NoSub,
-    NoJwkForKid,
-    NotPossibleToGetDecodeKey,
-    ErrorVerifying,
-    NoSignaturePresent,
+    InvalidJwt = 0,
+    InvalidJwks = 1,
+    InvalidJwk = 2,
+    InvalidJson = 3,
+    InvalidToken = 4,
+    TokenExpired = 5,
+    AlgorithmNotSupported = 6,
+    NoIssuer = 7,
+    NoSub = 8,
+    NoJwkForKid = 9,
+    NotPossibleToGetDecodeKey = 10,
+    ErrorVerifying = 11,
+    NoSignaturePresent = 12,
+    WeakKey = 13,
+    MissingKid = 14,
+    MissingExpiry = 15,
+    UnexpectedKid = 16,
+    InvalidIssuer = 17,
+    InvalidAudience = 18,
+    InsecureJwksUrl = 19,
+    JwksFetchFailed = 20,
+    MissingIssuedAt = 21,
+    TokenTooOld = 22,
+    HashMismatch = 23,
+    DuplicateKid = 24,
+    NonceMismatch = 25,
 }
 
+impl ErrorInJwt {
+    pub fn code(&self) -> u8 {
+        *self as u8
+    }
+
+    pub fn from_code(code: u8) -> Option<Self> {
+        match code {
+            0 => Some(Self::InvalidJwt),
+            1 => Some(Self::InvalidJwks),
+            2 => Some(Self::InvalidJwk),
+            3 => Some(Self::InvalidJson),
+            4 => Some(Self::InvalidToken),
+            5 => Some(Self::TokenExpired),
+            6 => Some(Self::AlgorithmNotSupported),
+            7 => Some(Self::NoIssuer),
+            8 => Some(Self::NoSub),
+            9 => Some(Self::NoJwkForKid),
+            10 => Some(Self::NotPossibleToGetDecodeKey),
+            11 => Some(Self::ErrorVerifying),
+            12 => Some(Self::NoSignaturePresent),
+            13 => Some(Self::WeakKey),
+            14 => Some(Self::MissingKid),
+            15 => Some(Self::MissingExpiry),
+            16 => Some(Self::UnexpectedKid),
+            17 => Some(Self::InvalidIssuer),
+            18 => Some(Self::InvalidAudience),
+            19 => Some(Self::InsecureJwksUrl),
+            20 => Some(Self::JwksFetchFailed),
+            21 => Some(Self::MissingIssuedAt),
+            22 => Some(Self::TokenTooOld),
+            23 => Some(Self::HashMismatch),
+            24 => Some(Self::DuplicateKid),
+            25 => Some(Self::NonceMismatch),
+            _ => None,
+        }
+    }
+}
+
+/// Minimum RSA modulus size (in bits) accepted by `validate_jwks` by default.
+pub const MIN_RSA_MODULUS_BITS: usize = 2048;
+
+/// Maximum nesting of arrays/objects accepted when parsing token payloads or JWKS documents.
+/// Well below serde_json's own limit of 128, which keeps worst-case parse cost predictable.
+pub const MAX_JSON_DEPTH: usize = 16;
+
+#[allow(clippy::large_enum_variant)]
 pub enum JwksEnum {
     Jwk(Jwk),
     Jwks(JwkSet),
@@ -28,49 +99,342 @@ pub enum JwksEnum {
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Claims {
-    pub aud: String,
+    #[serde(deserialize_with = "deserialize_audience")]
+    pub aud: Vec<String>,
+    /// Not a registered claim; tokens from third-party providers don't carry it
+    #[serde(default)]
     pub company: String,
+    /// Client-credentials tokens have no subject; see `VerifyOptions::require_sub`
+    #[serde(default)]
     pub sub: String,
-    pub exp: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub exp: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub iat: Option<u64>,
     pub iss: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub at_hash: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub c_hash: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub nonce: Option<String>,
+}
+
+// `aud` is either a single audience or an array of them (RFC 7519, section 4.1.3)
+fn deserialize_audience<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Vec<String>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Audience {
+        One(String),
+        Many(Vec<String>),
+    }
+    Ok(match Audience::deserialize(deserializer)? {
+        Audience::One(aud) => vec![aud],
+        Audience::Many(auds) => auds,
+    })
+}
+
+/// Outcome of `verify_jwt_report`, with the token's expiry available even when it's expired.
+pub struct VerifyReport {
+    pub result: Result<Claims, ErrorInJwt>,
+    /// The `exp` claim of a token whose signature verified.
+    pub expires_at: Option<u64>,
+    /// Seconds the token stays valid for, when it verified and has an `exp`.
+    pub remaining_secs: Option<u64>,
+}
+
+/// The checks of a verification, in the order they are walked.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VerifyStage {
+    Header,
+    KeyLookup,
+    Algorithm,
+    Signature,
+    Claims,
 }
 
-fn get_public_key(jwk: &Jwk) -> Option<DecodingKey> {
+/// A verification failure, along with the first check that failed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VerifyError {
+    pub stage: VerifyStage,
+    pub detail: ErrorInJwt,
+}
+
+impl VerifyError {
+    pub fn new(stage: VerifyStage, detail: ErrorInJwt) -> Self {
+        Self { stage, detail }
+    }
+
+    fn at(stage: VerifyStage) -> impl Fn(ErrorInJwt) -> Self {
+        move |detail| Self::new(stage, detail)
+    }
+}
+
+/// Policy applied to the claims of a token once its signature is verified.
+pub struct VerifyOptions {
+    /// Reject tokens without an `exp` claim.
+    pub require_exp: bool,
+    /// Reject tokens issued (`iat`) more than this many seconds ago, regardless of `exp`.
+    pub max_age_secs: Option<u64>,
+    /// Clock skew tolerated by the `exp` and `max_age_secs` checks.
+    pub leeway_secs: u64,
+    /// Reject tokens without a `sub` claim. Turn off for machine-to-machine tokens.
+    pub require_sub: bool,
+}
+
+impl Default for VerifyOptions {
+    fn default() -> Self {
+        Self {
+            require_exp: true,
+            max_age_secs: None,
+            leeway_secs: 0,
+            require_sub: true,
+        }
+    }
+}
+
+/// The `kty` is already tied to the parameters when parsing, but the declared `alg` isn't
+pub fn check_jwk_consistency(jwk: &Jwk) -> Result<(), ErrorInJwt> {
+    let Some(key_algorithm) = jwk.common.key_algorithm else {
+        return Ok(());
+    };
+    let consistent = match &jwk.algorithm {
+        AlgorithmParameters::RSA(_) => matches!(
+            key_algorithm,
+            KeyAlgorithm::RS256
+                | KeyAlgorithm::RS384
+                | KeyAlgorithm::RS512
+                | KeyAlgorithm::PS256
+                | KeyAlgorithm::PS384
+                | KeyAlgorithm::PS512
+                | KeyAlgorithm::RSA1_5
+                | KeyAlgorithm::RSA_OAEP
+                | KeyAlgorithm::RSA_OAEP_256
+        ),
+        AlgorithmParameters::EllipticCurve(ec_params) => matches!(
+            (key_algorithm, &ec_params.curve),
+            (KeyAlgorithm::ES256, EllipticCurve::P256) | (KeyAlgorithm::ES384, EllipticCurve::P384)
+        ),
+        AlgorithmParameters::OctetKey(_) => matches!(
+            key_algorithm,
+            KeyAlgorithm::HS256 | KeyAlgorithm::HS384 | KeyAlgorithm::HS512
+        ),
+        AlgorithmParameters::OctetKeyPair(_) => key_algorithm == KeyAlgorithm::EdDSA,
+    };
+    if !consistent {
+        return Err(ErrorInJwt::InvalidJwk);
+    }
+    Ok(())
+}
+
+fn get_public_key(jwk: &Jwk) -> Result<DecodingKey, ErrorInJwt> {
+    check_jwk_consistency(jwk)?;
     match &jwk.algorithm {
         AlgorithmParameters::RSA(rsa_params) => {
-            DecodingKey::from_rsa_components(&rsa_params.n, &rsa_params.e).ok()
+            DecodingKey::from_rsa_components(&rsa_params.n, &rsa_params.e)
+                .map_err(|_| ErrorInJwt::NotPossibleToGetDecodeKey)
+        }
+        AlgorithmParameters::EllipticCurve(ec_params) => {
+            DecodingKey::from_ec_components(&ec_params.x, &ec_params.y)
+                .map_err(|_| ErrorInJwt::NotPossibleToGetDecodeKey)
         }
-        _ => None,
+        AlgorithmParameters::OctetKeyPair(okp_params) => {
+            DecodingKey::from_ed_components(&okp_params.x)
+                .map_err(|_| ErrorInJwt::NotPossibleToGetDecodeKey)
+        }
+        AlgorithmParameters::OctetKey(_) => Err(ErrorInJwt::NotPossibleToGetDecodeKey),
     }
 }
 
-pub fn get_kid_from_token(the_header: &Header) -> Option<String> {
+pub fn get_kid_from_token(the_header: &Header) -> Result<String, ErrorInJwt> {
     match &the_header.kid {
-        Some(kid) if !kid.is_empty() => Some(kid.clone()),
-        _ => None,
+        Some(kid) if !kid.is_empty() => Ok(kid.clone()),
+        _ => Err(ErrorInJwt::MissingKid),
     }
 }
 
 // JWT auxiliar functions
 pub fn get_issuer(token: &TokenData<Claims>) -> Result<String, ErrorInJwt> {
-    let res = if token.claims.iss.is_empty() {
+    if token.claims.iss.is_empty() {
         Err(ErrorInJwt::NoIssuer)
     } else {
         Ok(token.claims.iss.clone())
-    };
-    res
+    }
 }
 
 pub fn get_sub(token: &TokenData<Claims>) -> Result<String, ErrorInJwt> {
-    let res = if token.claims.sub.is_empty() {
+    if token.claims.sub.is_empty() {
         Err(ErrorInJwt::NoSub)
     } else {
         Ok(token.claims.sub.clone())
+    }
+}
+
+// serde_json only lets its recursion limit be disabled, not lowered, so the nesting is
+// measured with a single pass over the bytes before handing them to the parser
+fn check_json_depth(bytes: &[u8], max_depth: usize) -> Result<(), ErrorInJwt> {
+    let mut depth = 0usize;
+    let mut in_string = false;
+    let mut escaped = false;
+    for &byte in bytes {
+        if in_string {
+            match byte {
+                _ if escaped => escaped = false,
+                b'\\' => escaped = true,
+                b'"' => in_string = false,
+                _ => {}
+            }
+            continue;
+        }
+        match byte {
+            b'"' => in_string = true,
+            b'{' | b'[' => {
+                depth += 1;
+                if depth > max_depth {
+                    return Err(ErrorInJwt::InvalidJson);
+                }
+            }
+            b'}' | b']' => depth = depth.saturating_sub(1),
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+// jsonwebtoken parses the header and payload with serde_json's defaults, so tokens headed
+// there are held to `MAX_JSON_DEPTH` first
+fn check_segments_depth(header: &str, payload: &str) -> Result<(), ErrorInJwt> {
+    for segment in [header, payload] {
+        let json = URL_SAFE_NO_PAD
+            .decode(segment)
+            .map_err(|_| ErrorInJwt::InvalidJwt)?;
+        check_json_depth(&json, MAX_JSON_DEPTH)?;
+    }
+    Ok(())
+}
+
+fn parse_json<T: DeserializeOwned>(bytes: &[u8]) -> Result<T, ErrorInJwt> {
+    check_json_depth(bytes, MAX_JSON_DEPTH)?;
+    serde_json::from_slice(bytes).map_err(|_| ErrorInJwt::InvalidJson)
+}
+
+fn decode_payload<T: DeserializeOwned>(token: &str) -> Result<T, ErrorInJwt> {
+    let payload = get_message(token).ok_or(ErrorInJwt::InvalidJwt)?;
+    let payload = URL_SAFE_NO_PAD
+        .decode(payload)
+        .map_err(|_| ErrorInJwt::InvalidJwt)?;
+    parse_json(&payload)
+}
+
+/// Decodes the payload without verifying the signature, for use after `verify_jwt`.
+pub fn get_claims(token: &str) -> Result<Claims, ErrorInJwt> {
+    decode_payload(token)
+}
+
+/// Source of the current time, in seconds since the Unix epoch, for the time-based checks.
+pub trait Clock {
+    fn now_secs(&self) -> u64;
+}
+
+/// Wall-clock time from the host.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now_secs(&self) -> u64 {
+        std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_secs())
+            .unwrap_or_default()
+    }
+}
+
+/// Always reports the same instant; meant for tests and replaying past verifications.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FixedClock(pub u64);
+
+impl Clock for FixedClock {
+    fn now_secs(&self) -> u64 {
+        self.0
+    }
+}
+
+/// Checks the claims `options` requires to be present.
+pub fn check_claims(claims: &Claims, options: &VerifyOptions) -> Result<(), ErrorInJwt> {
+    if options.require_exp && claims.exp.is_none() {
+        return Err(ErrorInJwt::MissingExpiry);
+    }
+    if options.require_sub && claims.sub.is_empty() {
+        return Err(ErrorInJwt::NoSub);
+    }
+    Ok(())
+}
+
+/// A token is expired once `now` reaches its `exp` (plus leeway), and too old once more than
+/// `max_age_secs` have passed since its `iat`
+pub fn check_timing(claims: &Claims, options: &VerifyOptions, now: u64) -> Result<(), ErrorInJwt> {
+    if let Some(exp) = claims.exp
+        && now >= exp.saturating_add(options.leeway_secs)
+    {
+        return Err(ErrorInJwt::TokenExpired);
+    }
+    if let Some(max_age_secs) = options.max_age_secs {
+        let iat = claims.iat.ok_or(ErrorInJwt::MissingIssuedAt)?;
+        if now.saturating_sub(iat) > max_age_secs.saturating_add(options.leeway_secs) {
+            return Err(ErrorInJwt::TokenTooOld);
+        }
+    }
+    Ok(())
+}
+
+/// Seconds until `exp` (zero once it has passed), or `None` for tokens that don't expire
+pub fn remaining_validity(claims: &Claims, now: u64) -> Option<u64> {
+    claims.exp.map(|exp| exp.saturating_sub(now))
+}
+
+/// OIDC `at_hash`/`c_hash`: left half of the hash of `value` with the token's own SHA variant
+pub fn oidc_half_hash(value: &str, alg: Algorithm) -> String {
+    let digest = match alg {
+        Algorithm::HS384 | Algorithm::ES384 | Algorithm::RS384 | Algorithm::PS384 => {
+            Sha384::digest(value).to_vec()
+        }
+        Algorithm::HS512 | Algorithm::RS512 | Algorithm::PS512 | Algorithm::EdDSA => {
+            Sha512::digest(value).to_vec()
+        }
+        _ => Sha256::digest(value).to_vec(),
     };
-    res
+    URL_SAFE_NO_PAD.encode(&digest[..digest.len() / 2])
+}
+
+fn check_hash_claim(
+    claim: Option<&str>,
+    value: Option<&str>,
+    alg: Algorithm,
+) -> Result<(), ErrorInJwt> {
+    match value {
+        Some(value) if claim != Some(oidc_half_hash(value, alg).as_str()) => {
+            Err(ErrorInJwt::HashMismatch)
+        }
+        _ => Ok(()),
+    }
+}
+
+/// When the client sent a `nonce` in the authentication request, the token must echo it back
+pub fn check_nonce(claims: &Claims, expected_nonce: Option<&str>) -> Result<(), ErrorInJwt> {
+    match expected_nonce {
+        Some(expected) if claims.nonce.as_deref() != Some(expected) => {
+            Err(ErrorInJwt::NonceMismatch)
+        }
+        _ => Ok(()),
+    }
 }
 
 // JWKs|JWK auxiliar functions
+
+/// Returns the first key (in document order) with the given `kid`. Sets that passed
+/// `validate_jwks` never hold more than one.
 pub fn get_jwk(jwt_kid: &str, jwks: &JwkSet) -> Option<Jwk> {
     jwks.keys.iter().find_map(|jwk| {
         if jwk.common.key_id.as_deref() == Some(jwt_kid) {
@@ -81,39 +445,450 @@ pub fn get_jwk(jwt_kid: &str, jwks: &JwkSet) -> Option<Jwk> {
     })
 }
 
-pub fn get_signature(token: &str) -> Option<String> {
-    match token.split('.').nth(2) {
-        Some(signature) => Some(signature.into()),
-        _ => None,
+/// A JWKS with a single key can be used by tokens without a `kid`; otherwise the `kid` must match
+pub fn select_jwk(token_header: &Header, jwks: &JwkSet) -> Result<Jwk, ErrorInJwt> {
+    match get_kid_from_token(token_header) {
+        Ok(jwt_kid) => get_jwk(&jwt_kid, jwks).ok_or(ErrorInJwt::NoJwkForKid),
+        Err(_) if jwks.keys.len() == 1 => Ok(jwks.keys[0].clone()),
+        Err(e) => Err(e),
+    }
+}
+
+fn key_algorithm_of(alg: Algorithm) -> KeyAlgorithm {
+    match alg {
+        Algorithm::HS256 => KeyAlgorithm::HS256,
+        Algorithm::HS384 => KeyAlgorithm::HS384,
+        Algorithm::HS512 => KeyAlgorithm::HS512,
+        Algorithm::ES256 => KeyAlgorithm::ES256,
+        Algorithm::ES384 => KeyAlgorithm::ES384,
+        Algorithm::RS256 => KeyAlgorithm::RS256,
+        Algorithm::RS384 => KeyAlgorithm::RS384,
+        Algorithm::RS512 => KeyAlgorithm::RS512,
+        Algorithm::PS256 => KeyAlgorithm::PS256,
+        Algorithm::PS384 => KeyAlgorithm::PS384,
+        Algorithm::PS512 => KeyAlgorithm::PS512,
+        Algorithm::EdDSA => KeyAlgorithm::EdDSA,
+    }
+}
+
+/// The token's `alg` is used for verification, so it must be one the key supports. A key that
+/// doesn't declare its `alg` takes the one from the token.
+pub fn check_algorithm(jwk: &Jwk, alg: Algorithm) -> Result<(), ErrorInJwt> {
+    if jwk
+        .common
+        .key_algorithm
+        .is_some_and(|key_algorithm| key_algorithm != key_algorithm_of(alg))
+    {
+        return Err(ErrorInJwt::AlgorithmNotSupported);
+    }
+    // Each key type only verifies its own family of algorithms, so e.g. an RSA public key can
+    // never be used as an HMAC secret. Symmetric (`oct`) keys don't belong in a public JWKS.
+    let supported = match &jwk.algorithm {
+        AlgorithmParameters::RSA(_) => matches!(
+            alg,
+            Algorithm::RS256
+                | Algorithm::RS384
+                | Algorithm::RS512
+                | Algorithm::PS256
+                | Algorithm::PS384
+                | Algorithm::PS512
+        ),
+        AlgorithmParameters::EllipticCurve(ec_params) => matches!(
+            (alg, &ec_params.curve),
+            (Algorithm::ES256, EllipticCurve::P256) | (Algorithm::ES384, EllipticCurve::P384)
+        ),
+        AlgorithmParameters::OctetKeyPair(okp_params) => {
+            alg == Algorithm::EdDSA && okp_params.curve == EllipticCurve::Ed25519
+        }
+        AlgorithmParameters::OctetKey(_) => false,
+    };
+    if !supported {
+        return Err(ErrorInJwt::AlgorithmNotSupported);
     }
+    Ok(())
+}
+
+fn rsa_modulus_bits(n: &str) -> Option<usize> {
+    let modulus = URL_SAFE_NO_PAD.decode(n).ok()?;
+    let first = modulus.iter().position(|byte| *byte != 0)?;
+    Some((modulus.len() - first) * 8 - modulus[first].leading_zeros() as usize)
+}
+
+/// Structural validation of a JWKS, rejecting keys weaker than the given policy
+pub fn validate_jwks(jwks: &JwkSet, min_rsa_modulus_bits: usize) -> Result<(), ErrorInJwt> {
+    for (i, jwk) in jwks.keys.iter().enumerate() {
+        check_jwk_consistency(jwk)?;
+        if let Some(kid) = jwk.common.key_id.as_deref()
+            && jwks.keys[..i]
+                .iter()
+                .any(|other| other.common.key_id.as_deref() == Some(kid))
+        {
+            return Err(ErrorInJwt::DuplicateKid);
+        }
+        if let AlgorithmParameters::RSA(rsa_params) = &jwk.algorithm {
+            let bits = rsa_modulus_bits(&rsa_params.n).ok_or(ErrorInJwt::InvalidJwk)?;
+            if bits < min_rsa_modulus_bits {
+                return Err(ErrorInJwt::WeakKey);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Keys from `new` take precedence; keys from `old` are kept only when `new` has nothing under
+/// their `kid`, so tokens signed by a key being rotated out keep verifying
+pub fn merge_jwks(old: &JwkSet, new: &JwkSet) -> JwkSet {
+    let mut keys = new.keys.clone();
+    keys.extend(
+        old.keys
+            .iter()
+            .filter(|jwk| match jwk.common.key_id.as_deref() {
+                Some(kid) => get_jwk(kid, new).is_none(),
+                None => true,
+            })
+            .cloned(),
+    );
+    JwkSet { keys }
+}
+
+/// Splits a compact JWS into its (header, payload, signature) segments. Nested (JWE-wrapped)
+/// or otherwise malformed tokens don't have exactly three non-empty parts.
+pub fn split_jws(token: &str) -> Result<(&str, &str, &str), ErrorInJwt> {
+    let mut segments = token.split('.');
+    match (
+        segments.next(),
+        segments.next(),
+        segments.next(),
+        segments.next(),
+    ) {
+        (Some(header), Some(payload), Some(signature), None)
+            if !header.is_empty() && !payload.is_empty() && !signature.is_empty() =>
+        {
+            Ok((header, payload, signature))
+        }
+        _ => Err(ErrorInJwt::InvalidToken),
+    }
+}
+
+pub fn get_signature(token: &str) -> Option<String> {
+    split_jws(token)
+        .ok()
+        .map(|(_, _, signature)| signature.into())
 }
 
 pub fn get_message(token: &str) -> Option<String> {
-    match token.split('.').nth(1) {
-        Some(message) => Some(message.into()),
-        _ => None,
+    split_jws(token).ok().map(|(_, payload, _)| payload.into())
+}
+
+/// The signature covers both the header and the payload
+pub fn get_signing_input(token: &str) -> Option<String> {
+    split_jws(token)
+        .ok()
+        .map(|(header, payload, _)| format!("{header}.{payload}"))
+}
+
+/// Signature-only check against a key the caller already holds
+pub fn verify_jwt_with_key(
+    token: &str,
+    key: &DecodingKey,
+    alg: Algorithm,
+) -> Result<bool, ErrorInJwt> {
+    let (header, payload, _) = split_jws(token)?;
+    check_segments_depth(header, payload)?;
+    check_signature(token, key, alg)
+}
+
+// Goes through `decode` (with every claim check off) since it refuses keys of another family
+// than `alg`, where the bare `crypto::verify` would panic. The segments must already have
+// passed `check_segments_depth`.
+fn check_signature(token: &str, key: &DecodingKey, alg: Algorithm) -> Result<bool, ErrorInJwt> {
+    let mut validation = Validation::new(alg);
+    validation.required_spec_claims.clear();
+    validation.validate_exp = false;
+    validation.validate_aud = false;
+    match decode::<serde_json::Value>(token, key, &validation) {
+        Ok(_) => Ok(true),
+        Err(e) => match e.kind() {
+            ErrorKind::InvalidSignature => Ok(false),
+            ErrorKind::InvalidAlgorithm => Err(ErrorInJwt::AlgorithmNotSupported),
+            _ => Err(ErrorInJwt::ErrorVerifying),
+        },
     }
 }
 
+// Also hands back the decoded header, so callers needing its `alg` don't decode it again
+fn verify_signature_staged(token: &str, jwks: &JwkSet) -> Result<(Header, bool), VerifyError> {
+    let (header, payload, _) = split_jws(token).map_err(VerifyError::at(VerifyStage::Header))?;
+    check_segments_depth(header, payload).map_err(VerifyError::at(VerifyStage::Header))?;
+    let token_header = decode_header(token)
+        .map_err(|_| VerifyError::new(VerifyStage::Header, ErrorInJwt::InvalidJwt))?;
+    let jwk = select_jwk(&token_header, jwks).map_err(VerifyError::at(VerifyStage::KeyLookup))?;
+    let decode_key = get_public_key(&jwk).map_err(VerifyError::at(VerifyStage::Algorithm))?;
+    check_algorithm(&jwk, token_header.alg).map_err(VerifyError::at(VerifyStage::Algorithm))?;
+
+    let verified = check_signature(token, &decode_key, token_header.alg)
+        .map_err(VerifyError::at(VerifyStage::Signature))?;
+    Ok((token_header, verified))
+}
+
 pub fn verify_jwt(token: &str, jwks: &JwkSet) -> Result<bool, ErrorInJwt> {
+    verify_signature_staged(token, jwks)
+        .map(|(_, verified)| verified)
+        .map_err(|e| e.detail)
+}
+
+#[derive(Deserialize)]
+struct RawJwkSet {
+    keys: Vec<serde_json::Value>,
+}
+
+// Key types this crate knows how to parse (RFC 7518, section 6.1, plus RFC 8037's `OKP`)
+const KNOWN_KEY_TYPES: [&str; 4] = ["RSA", "EC", "OKP", "oct"];
+
+/// Keys of a `kty` outside `KNOWN_KEY_TYPES` are skipped instead of failing the whole document,
+/// so providers can publish new key types. A known `kty` whose members don't parse is still an
+/// error, as is a document that isn't a JWKS at all.
+pub fn parse_jwks_lenient(jwks_bytes: &[u8]) -> Result<JwkSet, ErrorInJwt> {
+    let raw: RawJwkSet = parse_json(jwks_bytes).map_err(|_| ErrorInJwt::InvalidJwks)?;
+    let mut keys = Vec::with_capacity(raw.keys.len());
+    for key in raw.keys {
+        let kty = key["kty"].as_str();
+        if !kty.is_some_and(|kty| KNOWN_KEY_TYPES.contains(&kty)) {
+            log::warn!(
+                "skipping JWK of unknown kty {:?} (kid {:?})",
+                kty,
+                key["kid"]
+            );
+            continue;
+        }
+        keys.push(Jwk::deserialize(&key).map_err(|_| ErrorInJwt::InvalidJwk)?);
+    }
+    Ok(JwkSet { keys })
+}
+
+/// Parses raw JWKS bytes and applies the `validate_jwks` policy (modulus floor, unique kids),
+/// so every set loaded from bytes or fetched is held to the same rules
+pub fn load_jwks(jwks_bytes: &[u8]) -> Result<JwkSet, ErrorInJwt> {
+    let jwks = parse_jwks_lenient(jwks_bytes)?;
+    validate_jwks(&jwks, MIN_RSA_MODULUS_BITS)?;
+    Ok(jwks)
+}
+
+pub fn verify_jwt_from_bytes(token: &str, jwks_bytes: &[u8]) -> Result<bool, ErrorInJwt> {
+    let jwks = load_jwks(jwks_bytes)?;
+    verify_jwt(token, &jwks)
+}
+
+/// Rejects tokens not signed by the pinned `kid` before doing any crypto
+pub fn verify_jwt_with_kid(
+    token: &str,
+    jwks: &JwkSet,
+    expected_kid: Option<&str>,
+) -> Result<bool, ErrorInJwt> {
+    let (header, payload, _) = split_jws(token)?;
+    if let Some(expected_kid) = expected_kid {
+        check_segments_depth(header, payload)?;
+        let token_header = decode_header(token).map_err(|_| ErrorInJwt::InvalidJwt)?;
+        if get_kid_from_token(&token_header)? != expected_kid {
+            return Err(ErrorInJwt::UnexpectedKid);
+        }
+    }
+    verify_jwt(token, jwks)
+}
+
+/// Policy for the strict verification path, driven by the token's own algorithm
+/// (any one of the `expected_aud` values is accepted). `exp` stays required, but is checked
+/// by `verify_jwt_strict` against its clock rather than by `decode` against the host's, with
+/// its 60-second default leeway.
+pub fn validation_for(header: &Header, expected_iss: &str, expected_aud: &[&str]) -> Validation {
+    let mut validation = Validation::new(header.alg);
+    validation.set_issuer(&[expected_iss]);
+    validation.set_audience(expected_aud);
+    validation.validate_exp = false;
+    validation
+}
+
+fn map_decode_error(kind: &ErrorKind) -> ErrorInJwt {
+    match kind {
+        ErrorKind::InvalidSignature => ErrorInJwt::ErrorVerifying,
+        ErrorKind::ExpiredSignature => ErrorInJwt::TokenExpired,
+        ErrorKind::InvalidIssuer => ErrorInJwt::InvalidIssuer,
+        ErrorKind::InvalidAudience => ErrorInJwt::InvalidAudience,
+        ErrorKind::InvalidAlgorithm => ErrorInJwt::AlgorithmNotSupported,
+        ErrorKind::MissingRequiredClaim(claim) if claim == "exp" => ErrorInJwt::MissingExpiry,
+        ErrorKind::Json(_) => ErrorInJwt::InvalidJson,
+        _ => ErrorInJwt::InvalidToken,
+    }
+}
+
+/// Verifies the signature, issuer, audience and expiry, the latter against `clock`.
+pub fn verify_jwt_strict(
+    token: &str,
+    jwks: &JwkSet,
+    expected_iss: &str,
+    expected_aud: &[&str],
+    clock: &impl Clock,
+) -> Result<TokenData<Claims>, ErrorInJwt> {
+    let (header, payload, _) = split_jws(token)?;
+    check_segments_depth(header, payload)?;
     let token_header = decode_header(token).map_err(|_| ErrorInJwt::InvalidJwt)?;
-    let jwt_kid = get_kid_from_token(&token_header).ok_or(ErrorInJwt::InvalidJwt)?;
-    let jwk = get_jwk(&jwt_kid, &jwks).ok_or(ErrorInJwt::NoJwkForKid)?;
-    let decode_key = get_public_key(&jwk).ok_or(ErrorInJwt::NotPossibleToGetDecodeKey)?;
-    // // Get JWT info to use from Pallet?
-    // let token_data =decode::<Claims>(token, &decode_key, &Validation::new(Algorithm::RS256)).map_err(|_| ErrorInJwt::InvalidJwt)?;
-    // let _issuer = get_issuer(&token_data)?;
-    // let _subs = get_sub(&token_data)?;
-
-    // Extract signature
-    let signature = get_signature(&token).ok_or(ErrorInJwt::NoSignaturePresent)?;
-    let message = get_message(&token).ok_or(ErrorInJwt::NoSignaturePresent)?;
-    // Get JWK info
-    verify(
-        &signature,
-        message.as_bytes(),
-        &decode_key,
-        jsonwebtoken::Algorithm::RS256,
-    )
-    .map_err(|_| ErrorInJwt::ErrorVerifying)
+    let jwk = select_jwk(&token_header, jwks)?;
+    let decode_key = get_public_key(&jwk)?;
+    check_algorithm(&jwk, token_header.alg)?;
+    let validation = validation_for(&token_header, expected_iss, expected_aud);
+    let token_data = decode::<Claims>(token, &decode_key, &validation)
+        .map_err(|e| map_decode_error(e.kind()))?;
+    check_timing(
+        &token_data.claims,
+        &VerifyOptions::default(),
+        clock.now_secs(),
+    )?;
+    Ok(token_data)
+}
+
+/// Verifies the signature, then applies `options` to the claims.
+pub fn verify_jwt_with_options(
+    token: &str,
+    jwks: &JwkSet,
+    options: &VerifyOptions,
+    clock: &impl Clock,
+) -> Result<Claims, ErrorInJwt> {
+    verify_header_and_claims(token, jwks, options, clock).map(|(_, claims)| claims)
+}
+
+fn verify_header_and_claims(
+    token: &str,
+    jwks: &JwkSet,
+    options: &VerifyOptions,
+    clock: &impl Clock,
+) -> Result<(Header, Claims), ErrorInJwt> {
+    let (header, verified) = verify_signature_staged(token, jwks).map_err(|e| e.detail)?;
+    if !verified {
+        return Err(ErrorInJwt::ErrorVerifying);
+    }
+    let claims = get_claims(token)?;
+    check_claims(&claims, options)?;
+    check_timing(&claims, options, clock.now_secs())?;
+    Ok((header, claims))
+}
+
+/// Like `verify_jwt_with_options`, but reporting the token's expiry alongside the result.
+pub fn verify_jwt_report(
+    token: &str,
+    jwks: &JwkSet,
+    options: &VerifyOptions,
+    clock: &impl Clock,
+) -> VerifyReport {
+    let claims = match verify_jwt(token, jwks) {
+        Ok(true) => get_claims(token),
+        Ok(false) => Err(ErrorInJwt::ErrorVerifying),
+        Err(e) => Err(e),
+    };
+    let claims = match claims {
+        Ok(claims) => claims,
+        Err(e) => {
+            return VerifyReport {
+                result: Err(e),
+                expires_at: None,
+                remaining_secs: None,
+            };
+        }
+    };
+    let now = clock.now_secs();
+    let expires_at = claims.exp;
+    let result = check_claims(&claims, options)
+        .and_then(|_| check_timing(&claims, options, now))
+        .map(|_| claims);
+    let remaining_secs = result
+        .as_ref()
+        .ok()
+        .and_then(|claims| remaining_validity(claims, now));
+    VerifyReport {
+        result,
+        expires_at,
+        remaining_secs,
+    }
+}
+
+/// Like `verify_jwt_with_options`, but telling which stage of the verification failed.
+pub fn verify_jwt_staged(
+    token: &str,
+    jwks: &JwkSet,
+    options: &VerifyOptions,
+    clock: &impl Clock,
+) -> Result<Claims, VerifyError> {
+    if !verify_signature_staged(token, jwks)?.1 {
+        return Err(VerifyError::new(
+            VerifyStage::Signature,
+            ErrorInJwt::ErrorVerifying,
+        ));
+    }
+    let claims = get_claims(token).map_err(VerifyError::at(VerifyStage::Claims))?;
+    check_claims(&claims, options)
+        .and_then(|_| check_timing(&claims, options, clock.now_secs()))
+        .map_err(VerifyError::at(VerifyStage::Claims))?;
+    Ok(claims)
+}
+
+/// When given, the access token and authorization code must match the `at_hash`/`c_hash` claims
+pub fn verify_jwt_with_hashes(
+    token: &str,
+    jwks: &JwkSet,
+    options: &VerifyOptions,
+    access_token: Option<&str>,
+    code: Option<&str>,
+    clock: &impl Clock,
+) -> Result<Claims, ErrorInJwt> {
+    let (header, claims) = verify_header_and_claims(token, jwks, options, clock)?;
+    let alg = header.alg;
+    check_hash_claim(claims.at_hash.as_deref(), access_token, alg)?;
+    check_hash_claim(claims.c_hash.as_deref(), code, alg)?;
+    Ok(claims)
+}
+
+/// Like `verify_jwt_with_options`, and the `nonce` claim must match `expected_nonce` when given.
+pub fn verify_jwt_with_nonce(
+    token: &str,
+    jwks: &JwkSet,
+    options: &VerifyOptions,
+    expected_nonce: Option<&str>,
+    clock: &impl Clock,
+) -> Result<Claims, ErrorInJwt> {
+    let claims = verify_jwt_with_options(token, jwks, options, clock)?;
+    check_nonce(&claims, expected_nonce)?;
+    Ok(claims)
+}
+
+/// Like `verify_jwt_strict`, but returning the whole payload, provider-specific claims included
+pub fn verify_jwt_json(
+    token: &str,
+    jwks: &JwkSet,
+    expected_iss: &str,
+    expected_aud: &[&str],
+    clock: &impl Clock,
+) -> Result<serde_json::Value, ErrorInJwt> {
+    if !verify_jwt(token, jwks)? {
+        return Err(ErrorInJwt::ErrorVerifying);
+    }
+    let payload: serde_json::Value = decode_payload(token)?;
+
+    if payload["iss"].as_str() != Some(expected_iss) {
+        return Err(ErrorInJwt::InvalidIssuer);
+    }
+    let audience_matches = match &payload["aud"] {
+        serde_json::Value::String(aud) => expected_aud.contains(&aud.as_str()),
+        serde_json::Value::Array(auds) => auds
+            .iter()
+            .filter_map(serde_json::Value::as_str)
+            .any(|aud| expected_aud.contains(&aud)),
+        _ => false,
+    };
+    if !audience_matches {
+        return Err(ErrorInJwt::InvalidAudience);
+    }
+    let exp = payload["exp"].as_u64().ok_or(ErrorInJwt::MissingExpiry)?;
+    if clock.now_secs() >= exp {
+        return Err(ErrorInJwt::TokenExpired);
+    }
+    Ok(payload)
 }
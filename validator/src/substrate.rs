@@ -0,0 +1,80 @@
+//! Verifying JWS attestations signed by Substrate account keys.
+//!
+//! These tokens carry no JWKS: the signer is identified by its raw public key, and the
+//! header `alg` must match the key scheme (`EdDSA` for ed25519, `Sr25519` for sr25519 and
+//! `ES256K` for secp256k1 ECDSA, signed over the SHA-256 of the signing input).
+
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use sha2::{Digest, Sha256};
+use sp_core::{Pair, ecdsa, ed25519, sr25519};
+
+use crate::{ErrorInJwt, get_signing_input, parse_json, split_jws};
+
+/// Raw public key of a Substrate account, as found in its SS58 address.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SubstratePublic {
+    Ed25519([u8; 32]),
+    Sr25519([u8; 32]),
+    /// Compressed secp256k1 point.
+    Ecdsa([u8; 33]),
+}
+
+impl SubstratePublic {
+    fn jws_alg(&self) -> &'static str {
+        match self {
+            Self::Ed25519(_) => "EdDSA",
+            Self::Sr25519(_) => "Sr25519",
+            Self::Ecdsa(_) => "ES256K",
+        }
+    }
+}
+
+/// Verifies a compact JWS signed by `public`, whose scheme must match the header's `alg`.
+pub fn verify_substrate_jws(token: &str, public: &SubstratePublic) -> Result<bool, ErrorInJwt> {
+    let (header_segment, _, signature) = split_jws(token)?;
+    let header = URL_SAFE_NO_PAD
+        .decode(header_segment)
+        .map_err(|_| ErrorInJwt::InvalidJwt)?;
+    let header: serde_json::Value = parse_json(&header)?;
+    if header["alg"].as_str() != Some(public.jws_alg()) {
+        return Err(ErrorInJwt::AlgorithmNotSupported);
+    }
+    let signature = URL_SAFE_NO_PAD
+        .decode(signature)
+        .map_err(|_| ErrorInJwt::NoSignaturePresent)?;
+    let signing_input = get_signing_input(token).ok_or(ErrorInJwt::InvalidToken)?;
+    let signing_input = signing_input.as_bytes();
+
+    match public {
+        SubstratePublic::Ed25519(public) => {
+            let signature = signature.try_into().map_err(|_| ErrorInJwt::InvalidJwt)?;
+            Ok(ed25519::Pair::verify(
+                &ed25519::Signature::from_raw(signature),
+                signing_input,
+                &ed25519::Public::from_raw(*public),
+            ))
+        }
+        SubstratePublic::Sr25519(public) => {
+            let signature = signature.try_into().map_err(|_| ErrorInJwt::InvalidJwt)?;
+            Ok(sr25519::Pair::verify(
+                &sr25519::Signature::from_raw(signature),
+                signing_input,
+                &sr25519::Public::from_raw(*public),
+            ))
+        }
+        SubstratePublic::Ecdsa(public) => {
+            // JWS carries the bare 64-byte `r || s`, while sp_core verifies by recovery, so
+            // both recovery ids are tried
+            let signature: [u8; 64] = signature.try_into().map_err(|_| ErrorInJwt::InvalidJwt)?;
+            let digest: [u8; 32] = Sha256::digest(signing_input).into();
+            let public = ecdsa::Public::from_raw(*public);
+            Ok((0..2u8).any(|recovery_id| {
+                let mut raw = [0u8; 65];
+                raw[..64].copy_from_slice(&signature);
+                raw[64] = recovery_id;
+                ecdsa::Pair::verify_prehashed(&ecdsa::Signature::from_raw(raw), &digest, &public)
+            }))
+        }
+    }
+}
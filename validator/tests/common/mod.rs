@@ -0,0 +1,81 @@
+//! Public JWKs derived from the PEM fixtures, shared by the integration tests and benches so the
+//! published key material always matches the private keys that sign the test tokens.
+
+use base64::Engine;
+use base64::engine::general_purpose::{STANDARD, URL_SAFE_NO_PAD};
+use jsonwebtoken::jwk::{
+    AlgorithmParameters, CommonParameters, EllipticCurve, EllipticCurveKeyParameters,
+    EllipticCurveKeyType, Jwk, KeyAlgorithm, OctetKeyPairParameters, OctetKeyPairType,
+    RSAKeyParameters, RSAKeyType,
+};
+use ring::signature::{ECDSA_P256_SHA256_FIXED_SIGNING, EcdsaKeyPair, Ed25519KeyPair, KeyPair};
+use rsa::RsaPrivateKey;
+use rsa::pkcs8::DecodePrivateKey;
+use rsa::traits::PublicKeyParts;
+
+pub const RSA_PEM: &str = include_str!("../../test_key.pem");
+pub const EC_PEM: &str = include_str!("../../test_ec_key.pem");
+pub const ED25519_PEM: &str = include_str!("../../test_ed25519_key.pem");
+
+fn pem_to_der(pem: &str) -> Vec<u8> {
+    let body: String = pem
+        .lines()
+        .filter(|line| !line.starts_with("-----"))
+        .collect();
+    STANDARD.decode(body).unwrap()
+}
+
+fn common_parameters(kid: &str, alg: Option<KeyAlgorithm>) -> CommonParameters {
+    CommonParameters {
+        key_id: Some(kid.to_string()),
+        key_algorithm: alg,
+        ..Default::default()
+    }
+}
+
+// JWK for the public part of `test_key.pem` (RSA-2048)
+pub fn create_test_key_jwk(kid: &str) -> Jwk {
+    let private_key = RsaPrivateKey::from_pkcs8_pem(RSA_PEM).unwrap();
+    Jwk {
+        common: common_parameters(kid, Some(KeyAlgorithm::RS256)),
+        algorithm: AlgorithmParameters::RSA(RSAKeyParameters {
+            key_type: RSAKeyType::RSA,
+            n: URL_SAFE_NO_PAD.encode(private_key.n().to_bytes_be()),
+            e: URL_SAFE_NO_PAD.encode(private_key.e().to_bytes_be()),
+        }),
+    }
+}
+
+// JWK for the public part of `test_ec_key.pem` (P-256)
+pub fn create_test_ec_jwk(kid: &str) -> Jwk {
+    let key_pair = EcdsaKeyPair::from_pkcs8(
+        &ECDSA_P256_SHA256_FIXED_SIGNING,
+        &pem_to_der(EC_PEM),
+        &ring::rand::SystemRandom::new(),
+    )
+    .unwrap();
+    // Uncompressed SEC1 point: 0x04 || x || y
+    let point = key_pair.public_key().as_ref();
+    Jwk {
+        common: common_parameters(kid, Some(KeyAlgorithm::ES256)),
+        algorithm: AlgorithmParameters::EllipticCurve(EllipticCurveKeyParameters {
+            key_type: EllipticCurveKeyType::EC,
+            curve: EllipticCurve::P256,
+            x: URL_SAFE_NO_PAD.encode(&point[1..33]),
+            y: URL_SAFE_NO_PAD.encode(&point[33..]),
+        }),
+    }
+}
+
+// JWK for the public part of `test_ed25519_key.pem`, without a declared `alg`
+pub fn create_test_ed25519_jwk(kid: &str) -> Jwk {
+    let key_pair = Ed25519KeyPair::from_pkcs8_maybe_unchecked(&pem_to_der(ED25519_PEM)).unwrap();
+    Jwk {
+        common: common_parameters(kid, None),
+        algorithm: AlgorithmParameters::OctetKeyPair(OctetKeyPairParameters {
+            key_type: OctetKeyPairType::OctetKeyPair,
+            curve: EllipticCurve::Ed25519,
+            x: URL_SAFE_NO_PAD.encode(key_pair.public_key().as_ref()),
+        }),
+    }
+}
@@ -1,16 +1,26 @@
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
 use jsonwebtoken::jwk::{
     AlgorithmParameters, CommonParameters, Jwk, JwkSet, KeyAlgorithm, RSAKeyParameters, RSAKeyType,
 };
-use jsonwebtoken::{Algorithm, EncodingKey, Header, TokenData, encode};
+use jsonwebtoken::{Algorithm, DecodingKey, EncodingKey, Header, TokenData, encode};
+use rsa::RsaPrivateKey;
+use rsa::pkcs8::DecodePrivateKey;
+use rsa::traits::PublicKeyParts;
 // use serde_json::json;
 // use std::collections::HashMap;
 use validator::*;
 
-// Correct values
+mod common;
+use common::{
+    EC_PEM, ED25519_PEM, RSA_PEM, create_test_ec_jwk, create_test_ed25519_jwk, create_test_key_jwk,
+};
+
+// Toy RSA keys, far below `MIN_RSA_MODULUS_BITS`, for the weak-key checks
 // p=61 q=53 n=p*q=3233
 // phi(n) =(p-1)(q-1)=3120
 // e=17 d=2753
-fn create_correct_values() -> JwkSet {
+fn create_weak_jwks() -> JwkSet {
     let correct_jwk_1: Jwk = Jwk {
         common: CommonParameters {
             key_algorithm: Some(KeyAlgorithm::RS256),
@@ -44,11 +54,15 @@ fn create_correct_values() -> JwkSet {
 
 fn create_test_claims(exp: u64) -> Claims {
     Claims {
-        aud: "test_audience".into(),
+        aud: vec!["test_audience".into()],
         sub: "user123".into(),
         company: "test_company".into(),
-        exp,
+        exp: Some(exp),
+        iat: None,
         iss: "test_issuer".into(),
+        at_hash: None,
+        c_hash: None,
+        nonce: None,
     }
 }
 
@@ -67,6 +81,44 @@ fn create_test_jwk(kid: &str, n: &str, e: &str) -> Jwk {
     }
 }
 
+// The set holding just the `test_key.pem` JWK
+fn create_test_key_set(kid: &str) -> JwkSet {
+    JwkSet {
+        keys: vec![create_test_key_jwk(kid)],
+    }
+}
+
+// Serialized JWKS, as it would be stored on chain
+fn create_test_jwks(kid: &str) -> Vec<u8> {
+    serde_json::to_vec(&create_test_key_set(kid)).unwrap()
+}
+
+fn create_test_es256_token(kid: &str) -> String {
+    let mut header = Header::new(Algorithm::ES256);
+    header.kid = Some(kid.to_string());
+    encode(
+        &header,
+        &create_test_claims(u64::MAX),
+        &EncodingKey::from_ec_pem(EC_PEM.as_bytes()).unwrap(),
+    )
+    .unwrap()
+}
+
+fn create_test_token(kid: &str) -> String {
+    create_test_token_with_claims(kid, &create_test_claims(u64::MAX))
+}
+
+fn create_test_token_with_claims(kid: &str, claims: &impl serde::Serialize) -> String {
+    let mut header = Header::new(Algorithm::RS256);
+    header.kid = Some(kid.to_string());
+    encode(
+        &header,
+        claims,
+        &EncodingKey::from_rsa_pem(RSA_PEM.as_bytes()).unwrap(),
+    )
+    .unwrap()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -96,14 +148,20 @@ mod tests {
     #[test]
     fn test_get_kid_from_token_none() {
         let header = Header::new(Algorithm::RS256);
-        assert_eq!(get_kid_from_token(&header), None);
+        assert!(matches!(
+            get_kid_from_token(&header),
+            Err(ErrorInJwt::MissingKid)
+        ));
     }
 
     #[test]
     fn test_get_kid_from_token_empty() {
         let mut header = Header::new(Algorithm::RS256);
         header.kid = Some("".to_string());
-        assert_eq!(get_kid_from_token(&header), None);
+        assert!(matches!(
+            get_kid_from_token(&header),
+            Err(ErrorInJwt::MissingKid)
+        ));
     }
 
     // #[test]
@@ -160,6 +218,7 @@ mod tests {
         let token = "aaa.bbb.ccc";
         assert_eq!(get_signature(token).unwrap(), "ccc");
         assert_eq!(get_message(token).unwrap(), "bbb");
+        assert_eq!(get_signing_input(token).unwrap(), "aaa.bbb");
     }
 
     #[test]
@@ -169,6 +228,7 @@ mod tests {
         for token in invalid_tokens {
             assert_eq!(get_signature(token), None);
             assert_eq!(get_message(token), None);
+            assert_eq!(get_signing_input(token), None);
         }
     }
 
@@ -192,10 +252,8 @@ mod tests {
 
     #[test]
     fn test_verify_jwt_success() {
-        let n = "3233"; // Example RSA modulus
-        let e = "17"; // Example RSA public exponent
         let kid = "test_kid";
-        let jwk = create_test_jwk(kid, n, e);
+        let jwk = create_test_key_jwk(kid);
         let jwks = JwkSet { keys: vec![jwk] };
 
         let mut header = Header::new(Algorithm::RS256);
@@ -205,12 +263,12 @@ mod tests {
         let token = encode(
             &header,
             &claims,
-            &EncodingKey::from_rsa_pem(include_bytes!("../test_key.pem")).unwrap(),
+            &EncodingKey::from_rsa_pem(RSA_PEM.as_bytes()).unwrap(),
         )
         .unwrap();
 
         let result = verify_jwt(&token, &jwks);
-        assert!(result.is_ok());
+        assert_eq!(result, Ok(true));
     }
 
     #[test]
@@ -229,12 +287,12 @@ mod tests {
         let token = encode(
             &header,
             &claims,
-            &EncodingKey::from_rsa_pem(include_bytes!("../test_key.pem")).unwrap(),
+            &EncodingKey::from_rsa_pem(RSA_PEM.as_bytes()).unwrap(),
         )
         .unwrap();
         assert!(matches!(
             verify_jwt(&token, &jwks),
-            Err(ErrorInJwt::InvalidJwt)
+            Err(ErrorInJwt::MissingKid)
         ));
 
         // Test missing JWK
@@ -242,7 +300,7 @@ mod tests {
         let token = encode(
             &header,
             &claims,
-            &EncodingKey::from_rsa_pem(include_bytes!("../test_key.pem")).unwrap(),
+            &EncodingKey::from_rsa_pem(RSA_PEM.as_bytes()).unwrap(),
         )
         .unwrap();
         assert!(matches!(
@@ -250,4 +308,1299 @@ mod tests {
             Err(ErrorInJwt::NoJwkForKid)
         ));
     }
+
+    #[test]
+    fn test_verify_jwt_from_bytes() {
+        let kid = "test_kid";
+        let jwks_bytes = create_test_jwks(kid);
+        let token = create_test_token(kid);
+
+        assert_eq!(verify_jwt_from_bytes(&token, &jwks_bytes), Ok(true));
+    }
+
+    #[test]
+    fn test_verify_jwt_from_bytes_invalid_jwks() {
+        let token = create_test_token("test_kid");
+
+        assert!(matches!(
+            verify_jwt_from_bytes(&token, b"not a jwks"),
+            Err(ErrorInJwt::InvalidJwks)
+        ));
+    }
+
+    #[test]
+    fn test_json_nested_beyond_limit_is_rejected() {
+        let depth = MAX_JSON_DEPTH + 1;
+        let nested = format!("{}{}", "[".repeat(depth), "]".repeat(depth));
+
+        let payload = URL_SAFE_NO_PAD.encode(format!(r#"{{"sub":"user123","extra":{nested}}}"#));
+        let token = format!("e30.{payload}.c2ln");
+        assert!(matches!(get_claims(&token), Err(ErrorInJwt::InvalidJson)));
+
+        let jwks_bytes = format!(r#"{{"keys":[],"extra":{nested}}}"#);
+        assert!(matches!(
+            verify_jwt_from_bytes(&create_test_token("test_kid"), jwks_bytes.as_bytes()),
+            Err(ErrorInJwt::InvalidJwks)
+        ));
+
+        let within = "[".repeat(MAX_JSON_DEPTH - 1) + &"]".repeat(MAX_JSON_DEPTH - 1);
+        let payload = URL_SAFE_NO_PAD.encode(format!(
+            r#"{{"aud":"a","company":"c","sub":"s","iss":"i","extra":{within},"note":"[[[["}}"#
+        ));
+        assert!(get_claims(&format!("e30.{payload}.c2ln")).is_ok());
+
+        // A validly signed token is rejected before jsonwebtoken parses it
+        let kid = "test_kid";
+        let jwks = create_test_key_set(kid);
+        let nested: serde_json::Value = serde_json::from_str(&nested).unwrap();
+        let token = create_test_token_with_claims(
+            kid,
+            &serde_json::json!({
+                "iss": "test_issuer",
+                "aud": "test_audience",
+                "sub": "user123",
+                "exp": u64::MAX,
+                "extra": nested,
+            }),
+        );
+        assert!(matches!(
+            verify_jwt(&token, &jwks),
+            Err(ErrorInJwt::InvalidJson)
+        ));
+        assert!(matches!(
+            verify_jwt_strict(
+                &token,
+                &jwks,
+                "test_issuer",
+                &["test_audience"],
+                &SystemClock
+            ),
+            Err(ErrorInJwt::InvalidJson)
+        ));
+        assert!(matches!(
+            verify_jwt_with_kid(&token, &jwks, Some(kid)),
+            Err(ErrorInJwt::InvalidJson)
+        ));
+        // The pinned `kid` is only read once the header passed the same check
+        let header = URL_SAFE_NO_PAD
+            .encode(serde_json::json!({ "alg": "RS256", "kid": kid, "extra": nested }).to_string());
+        assert!(matches!(
+            verify_jwt_with_kid(&format!("{header}.e30.c2ln"), &jwks, Some("other_kid")),
+            Err(ErrorInJwt::InvalidJson)
+        ));
+        let key = DecodingKey::from_jwk(&jwks.keys[0]).unwrap();
+        assert!(matches!(
+            verify_jwt_with_key(&token, &key, Algorithm::RS256),
+            Err(ErrorInJwt::InvalidJson)
+        ));
+    }
+
+    #[test]
+    fn test_verify_jwt_from_bytes_skips_unknown_key_types() {
+        let kid = "test_kid";
+        let mut jwks = serde_json::json!({ "keys": [create_test_key_jwk(kid)] });
+        jwks["keys"].as_array_mut().unwrap().insert(
+            0,
+            serde_json::json!({ "kty": "PQC", "kid": "pq_kid", "pub": "AAAA" }),
+        );
+        let jwks_bytes = serde_json::to_vec(&jwks).unwrap();
+
+        assert!(serde_json::from_slice::<JwkSet>(&jwks_bytes).is_err());
+        assert_eq!(parse_jwks_lenient(&jwks_bytes).unwrap().keys.len(), 1);
+        assert_eq!(
+            verify_jwt_from_bytes(&create_test_token(kid), &jwks_bytes),
+            Ok(true)
+        );
+    }
+
+    #[test]
+    fn test_verify_jwt_from_bytes_applies_key_policy() {
+        let kid = "test_kid";
+        let token = create_test_token(kid);
+
+        // The toy moduli are rejected before any signature check
+        let weak = serde_json::to_vec(&create_weak_jwks()).unwrap();
+        assert!(matches!(
+            verify_jwt_from_bytes(&token, &weak),
+            Err(ErrorInJwt::WeakKey)
+        ));
+
+        let duplicated = serde_json::to_vec(&JwkSet {
+            keys: vec![create_test_key_jwk(kid), create_test_key_jwk(kid)],
+        })
+        .unwrap();
+        assert!(matches!(
+            verify_jwt_from_bytes(&token, &duplicated),
+            Err(ErrorInJwt::DuplicateKid)
+        ));
+        assert!(matches!(
+            load_jwks(&duplicated),
+            Err(ErrorInJwt::DuplicateKid)
+        ));
+    }
+
+    #[test]
+    fn test_verify_jwt_from_bytes_unknown_kid() {
+        let jwks_bytes = create_test_jwks("other_kid");
+        let token = create_test_token("test_kid");
+
+        assert!(matches!(
+            verify_jwt_from_bytes(&token, &jwks_bytes),
+            Err(ErrorInJwt::NoJwkForKid)
+        ));
+    }
+
+    #[test]
+    fn test_validate_jwks_accepts_strong_key() {
+        let jwks = create_test_key_set("test_kid");
+
+        assert!(validate_jwks(&jwks, MIN_RSA_MODULUS_BITS).is_ok());
+    }
+
+    #[test]
+    fn test_validate_jwks_rejects_duplicate_kid() {
+        let jwks = JwkSet {
+            keys: vec![
+                create_test_key_jwk("test_kid"),
+                create_test_ed25519_jwk("other_kid"),
+                create_test_key_jwk("test_kid"),
+            ],
+        };
+
+        assert!(matches!(
+            validate_jwks(&jwks, MIN_RSA_MODULUS_BITS),
+            Err(ErrorInJwt::DuplicateKid)
+        ));
+    }
+
+    #[test]
+    fn test_merge_jwks_keeps_rotated_out_keys() {
+        let stale = create_test_jwk("new_kid", "AQAB", "AQAB");
+        let old = JwkSet {
+            keys: vec![create_test_key_jwk("old_kid"), stale],
+        };
+        let new = create_test_key_set("new_kid");
+
+        let merged = merge_jwks(&old, &new);
+        assert_eq!(merged.keys.len(), 2);
+        assert!(validate_jwks(&merged, MIN_RSA_MODULUS_BITS).is_ok());
+        assert_eq!(verify_jwt(&create_test_token("old_kid"), &merged), Ok(true));
+        assert_eq!(verify_jwt(&create_test_token("new_kid"), &merged), Ok(true));
+    }
+
+    #[test]
+    fn test_validate_jwks_rejects_weak_key() {
+        // 512-bit modulus
+        let n = URL_SAFE_NO_PAD.encode([0xffu8; 64]);
+        let jwks = JwkSet {
+            keys: vec![
+                create_test_key_jwk("strong_kid"),
+                create_test_jwk("weak_kid", &n, "AQAB"),
+            ],
+        };
+
+        assert!(matches!(
+            validate_jwks(&jwks, MIN_RSA_MODULUS_BITS),
+            Err(ErrorInJwt::WeakKey)
+        ));
+        assert!(validate_jwks(&jwks, 512).is_ok());
+    }
+
+    #[test]
+    fn test_verify_jwt_with_options_requires_exp() {
+        let kid = "test_kid";
+        let jwks = create_test_key_set(kid);
+        let mut claims = create_test_claims(0);
+        claims.exp = None;
+        let token = create_test_token_with_claims(kid, &claims);
+
+        assert!(matches!(
+            verify_jwt_with_options(&token, &jwks, &VerifyOptions::default(), &SystemClock),
+            Err(ErrorInJwt::MissingExpiry)
+        ));
+    }
+
+    #[test]
+    fn test_verify_jwt_with_options_allows_missing_exp() {
+        let kid = "test_kid";
+        let jwks = create_test_key_set(kid);
+        let mut claims = create_test_claims(0);
+        claims.exp = None;
+        let token = create_test_token_with_claims(kid, &claims);
+        let options = VerifyOptions {
+            require_exp: false,
+            ..Default::default()
+        };
+
+        let claims = verify_jwt_with_options(&token, &jwks, &options, &SystemClock).unwrap();
+        assert_eq!(claims.exp, None);
+        assert_eq!(claims.sub, "user123");
+    }
+
+    #[test]
+    fn test_verify_jwt_with_options_checks_timing() {
+        let kid = "test_kid";
+        let jwks = create_test_key_set(kid);
+        let options = VerifyOptions::default();
+
+        let expired = create_test_token_with_claims(kid, &create_test_claims(1_000));
+        assert!(verify_jwt_with_options(&expired, &jwks, &options, &FixedClock(999)).is_ok());
+        assert!(matches!(
+            verify_jwt_with_options(&expired, &jwks, &options, &FixedClock(1_000)),
+            Err(ErrorInJwt::TokenExpired)
+        ));
+
+        let mut claims = create_test_claims(u64::MAX);
+        claims.iat = Some(0);
+        let issued_long_ago = create_test_token_with_claims(kid, &claims);
+        let options = VerifyOptions {
+            max_age_secs: Some(1),
+            ..Default::default()
+        };
+        assert!(matches!(
+            verify_jwt_with_options(&issued_long_ago, &jwks, &options, &FixedClock(10)),
+            Err(ErrorInJwt::TokenTooOld)
+        ));
+    }
+
+    #[test]
+    fn test_verify_jwt_with_nonce() {
+        let kid = "test_kid";
+        let jwks = create_test_key_set(kid);
+        let nonce = Some("n-0S6_WzA2Mj");
+        let mut claims = create_test_claims(1_000);
+        claims.nonce = nonce.map(String::from);
+        let token = create_test_token_with_claims(kid, &claims);
+        let options = VerifyOptions::default();
+        let clock = FixedClock(900);
+
+        assert!(verify_jwt_with_nonce(&token, &jwks, &options, nonce, &clock).is_ok());
+        assert!(verify_jwt_with_nonce(&token, &jwks, &options, None, &clock).is_ok());
+        assert!(matches!(
+            verify_jwt_with_nonce(&token, &jwks, &options, Some("other-nonce"), &clock),
+            Err(ErrorInJwt::NonceMismatch)
+        ));
+
+        // A matching nonce doesn't make an expired token replayable
+        assert!(matches!(
+            verify_jwt_with_nonce(&token, &jwks, &options, nonce, &FixedClock(1_000)),
+            Err(ErrorInJwt::TokenExpired)
+        ));
+
+        let token = create_test_token(kid);
+        assert!(matches!(
+            verify_jwt_with_nonce(&token, &jwks, &options, nonce, &clock),
+            Err(ErrorInJwt::NonceMismatch)
+        ));
+    }
+
+    #[test]
+    fn test_verify_jwt_with_options_require_sub() {
+        let kid = "test_kid";
+        let jwks = create_test_key_set(kid);
+        let mut header = Header::new(Algorithm::RS256);
+        header.kid = Some(kid.to_string());
+        let payload = serde_json::json!({
+            "aud": "test_audience",
+            "company": "company",
+            "iss": "test_issuer",
+            "exp": u64::MAX,
+        });
+        let token = encode(
+            &header,
+            &payload,
+            &EncodingKey::from_rsa_pem(RSA_PEM.as_bytes()).unwrap(),
+        )
+        .unwrap();
+
+        assert!(matches!(
+            verify_jwt_with_options(&token, &jwks, &VerifyOptions::default(), &SystemClock),
+            Err(ErrorInJwt::NoSub)
+        ));
+
+        let options = VerifyOptions {
+            require_sub: false,
+            ..Default::default()
+        };
+        let claims = verify_jwt_with_options(&token, &jwks, &options, &SystemClock).unwrap();
+        assert!(claims.sub.is_empty());
+    }
+
+    #[test]
+    fn test_verify_jwt_with_kid_matching() {
+        let kid = "test_kid";
+        let jwks = create_test_key_set(kid);
+        let token = create_test_token(kid);
+
+        assert!(matches!(
+            verify_jwt_with_kid(&token, &jwks, Some(kid)),
+            Ok(true)
+        ));
+        assert!(matches!(verify_jwt_with_kid(&token, &jwks, None), Ok(true)));
+    }
+
+    #[test]
+    fn test_verify_jwt_with_kid_mismatching() {
+        let jwks = JwkSet {
+            keys: vec![
+                create_test_key_jwk("test_kid"),
+                create_test_key_jwk("pinned_kid"),
+            ],
+        };
+        let token = create_test_token("test_kid");
+
+        assert!(matches!(
+            verify_jwt_with_kid(&token, &jwks, Some("pinned_kid")),
+            Err(ErrorInJwt::UnexpectedKid)
+        ));
+    }
+
+    #[test]
+    fn test_verify_jwt_strict_success() {
+        let kid = "test_kid";
+        let jwks = create_test_key_set(kid);
+        let token = create_test_token(kid);
+
+        let token_data = verify_jwt_strict(
+            &token,
+            &jwks,
+            "test_issuer",
+            &["test_audience"],
+            &SystemClock,
+        )
+        .unwrap();
+        assert_eq!(token_data.claims.sub, "user123");
+    }
+
+    #[test]
+    fn test_verify_jwt_strict_without_company() {
+        let kid = "test_kid";
+        let jwks = create_test_key_set(kid);
+        let token = create_test_token_with_claims(
+            kid,
+            &serde_json::json!({
+                "iss": "test_issuer",
+                "aud": "test_audience",
+                "sub": "user123",
+                "exp": u64::MAX,
+            }),
+        );
+
+        let token_data = verify_jwt_strict(
+            &token,
+            &jwks,
+            "test_issuer",
+            &["test_audience"],
+            &SystemClock,
+        )
+        .unwrap();
+        assert!(token_data.claims.company.is_empty());
+        assert!(
+            verify_jwt_with_options(&token, &jwks, &VerifyOptions::default(), &SystemClock).is_ok()
+        );
+    }
+
+    #[test]
+    fn test_validation_for_rejects_wrong_audience() {
+        let kid = "test_kid";
+        let jwks = create_test_key_set(kid);
+        let token = create_test_token(kid);
+
+        let mut header = Header::new(Algorithm::RS256);
+        header.kid = Some(kid.to_string());
+        let validation = validation_for(&header, "test_issuer", &["other_audience"]);
+        assert_eq!(validation.algorithms, vec![Algorithm::RS256]);
+        // Expiry is checked by `verify_jwt_strict` against its clock, not by `decode`
+        assert!(!validation.validate_exp);
+        assert!(validation.required_spec_claims.contains("exp"));
+
+        assert!(matches!(
+            verify_jwt_strict(
+                &token,
+                &jwks,
+                "test_issuer",
+                &["other_audience"],
+                &SystemClock
+            ),
+            Err(ErrorInJwt::InvalidAudience)
+        ));
+        assert!(matches!(
+            verify_jwt_strict(
+                &token,
+                &jwks,
+                "other_issuer",
+                &["test_audience"],
+                &SystemClock
+            ),
+            Err(ErrorInJwt::InvalidIssuer)
+        ));
+    }
+
+    #[test]
+    fn test_verify_jwt_strict_uses_clock_without_leeway() {
+        let kid = "test_kid";
+        let jwks = create_test_key_set(kid);
+        let token = create_test_token_with_claims(kid, &create_test_claims(1_000));
+
+        assert!(
+            verify_jwt_strict(
+                &token,
+                &jwks,
+                "test_issuer",
+                &["test_audience"],
+                &FixedClock(999)
+            )
+            .is_ok()
+        );
+        // No leeway: the token is expired from its `exp` onwards
+        assert!(matches!(
+            verify_jwt_strict(
+                &token,
+                &jwks,
+                "test_issuer",
+                &["test_audience"],
+                &FixedClock(1_000)
+            ),
+            Err(ErrorInJwt::TokenExpired)
+        ));
+
+        let mut claims = create_test_claims(0);
+        claims.exp = None;
+        let token = create_test_token_with_claims(kid, &claims);
+        assert!(matches!(
+            verify_jwt_strict(
+                &token,
+                &jwks,
+                "test_issuer",
+                &["test_audience"],
+                &FixedClock(0)
+            ),
+            Err(ErrorInJwt::MissingExpiry)
+        ));
+    }
+
+    #[test]
+    fn test_verify_jwt_rejects_extra_segments() {
+        let kid = "test_kid";
+        let jwks = create_test_key_set(kid);
+        let token = format!("{}.extra", create_test_token(kid));
+
+        assert!(matches!(
+            verify_jwt(&token, &jwks),
+            Err(ErrorInJwt::InvalidToken)
+        ));
+        assert!(matches!(
+            verify_jwt_with_kid(&token, &jwks, Some("other_kid")),
+            Err(ErrorInJwt::InvalidToken)
+        ));
+        assert!(matches!(
+            verify_jwt_strict(
+                &token,
+                &jwks,
+                "test_issuer",
+                &["test_audience"],
+                &SystemClock
+            ),
+            Err(ErrorInJwt::InvalidToken)
+        ));
+    }
+
+    #[test]
+    fn test_error_codes_round_trip() {
+        let errors = [
+            ErrorInJwt::InvalidJwt,
+            ErrorInJwt::InvalidJwks,
+            ErrorInJwt::InvalidJwk,
+            ErrorInJwt::InvalidJson,
+            ErrorInJwt::InvalidToken,
+            ErrorInJwt::TokenExpired,
+            ErrorInJwt::AlgorithmNotSupported,
+            ErrorInJwt::NoIssuer,
+            ErrorInJwt::NoSub,
+            ErrorInJwt::NoJwkForKid,
+            ErrorInJwt::NotPossibleToGetDecodeKey,
+            ErrorInJwt::ErrorVerifying,
+            ErrorInJwt::NoSignaturePresent,
+            ErrorInJwt::WeakKey,
+            ErrorInJwt::MissingKid,
+            ErrorInJwt::MissingExpiry,
+            ErrorInJwt::UnexpectedKid,
+            ErrorInJwt::InvalidIssuer,
+            ErrorInJwt::InvalidAudience,
+            ErrorInJwt::InsecureJwksUrl,
+            ErrorInJwt::JwksFetchFailed,
+            ErrorInJwt::MissingIssuedAt,
+            ErrorInJwt::TokenTooOld,
+            ErrorInJwt::HashMismatch,
+            ErrorInJwt::DuplicateKid,
+            ErrorInJwt::NonceMismatch,
+        ];
+
+        let mut codes: Vec<u8> = errors.iter().map(ErrorInJwt::code).collect();
+        for (error, code) in errors.iter().zip(&codes) {
+            assert_eq!(ErrorInJwt::from_code(*code), Some(*error));
+        }
+        codes.sort();
+        codes.dedup();
+        assert_eq!(codes.len(), errors.len());
+        assert_eq!(ErrorInJwt::from_code(u8::MAX), None);
+    }
+
+    // Serves `body` with the given `Cache-Control` header, counting the requests it answers
+    #[cfg(feature = "http")]
+    fn serve_jwks(
+        body: Vec<u8>,
+        cache_control: &'static str,
+    ) -> (String, std::sync::Arc<std::sync::atomic::AtomicUsize>) {
+        use std::io::{BufRead, BufReader, Write};
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/jwks.json", listener.local_addr().unwrap());
+        let requests = std::sync::Arc::new(AtomicUsize::new(0));
+        let served = requests.clone();
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let mut line = String::new();
+                while reader.read_line(&mut line).unwrap() > 2 {
+                    line.clear();
+                }
+                served.fetch_add(1, Ordering::SeqCst);
+                let head = format!(
+                    "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nCache-Control: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                    cache_control,
+                    body.len()
+                );
+                // Clients may hang up early (e.g. on an oversized body)
+                let _ = stream
+                    .write_all(head.as_bytes())
+                    .and_then(|_| stream.write_all(&body));
+            }
+        });
+        (url, requests)
+    }
+
+    #[cfg(feature = "http")]
+    #[test]
+    fn test_verify_with_jwks_url_uses_cache() {
+        use std::sync::atomic::Ordering;
+        use validator::fetch::JwksCache;
+
+        let kid = "test_kid";
+        let (url, requests) = serve_jwks(create_test_jwks(kid), "public, max-age=60");
+        let token = create_test_token(kid);
+        let mut cache = JwksCache::new().allow_http();
+
+        assert!(matches!(cache.verify_with_jwks_url(&token, &url), Ok(true)));
+        assert!(matches!(cache.verify_with_jwks_url(&token, &url), Ok(true)));
+        assert_eq!(requests.load(Ordering::SeqCst), 1);
+    }
+
+    #[cfg(feature = "http")]
+    #[test]
+    fn test_verify_with_jwks_url_no_store() {
+        use std::sync::atomic::Ordering;
+        use validator::fetch::JwksCache;
+
+        let kid = "test_kid";
+        let (url, requests) = serve_jwks(create_test_jwks(kid), "no-store, max-age=60");
+        let token = create_test_token(kid);
+        let mut cache = JwksCache::new().allow_http();
+
+        assert!(matches!(cache.verify_with_jwks_url(&token, &url), Ok(true)));
+        assert!(matches!(cache.verify_with_jwks_url(&token, &url), Ok(true)));
+        assert_eq!(requests.load(Ordering::SeqCst), 2);
+    }
+
+    #[cfg(feature = "http")]
+    #[test]
+    fn test_verify_with_jwks_url_rejects_weak_keys() {
+        use validator::fetch::JwksCache;
+
+        let weak = serde_json::to_vec(&create_weak_jwks()).unwrap();
+        let (url, _) = serve_jwks(weak, "public, max-age=60");
+        let token = create_test_token("test_kid");
+        let mut cache = JwksCache::new().allow_http();
+
+        assert!(matches!(
+            cache.verify_with_jwks_url(&token, &url),
+            Err(ErrorInJwt::WeakKey)
+        ));
+    }
+
+    #[cfg(feature = "http")]
+    #[test]
+    fn test_verify_with_jwks_url_rejects_oversized_body() {
+        use validator::fetch::{JwksCache, MAX_JWKS_BYTES};
+
+        let padding = "x".repeat(MAX_JWKS_BYTES as usize);
+        let body = format!(r#"{{"keys":[],"padding":"{padding}"}}"#).into_bytes();
+        let (url, _) = serve_jwks(body, "public, max-age=60");
+        let mut cache = JwksCache::new().allow_http();
+
+        assert!(matches!(
+            cache.get_jwks(&url),
+            Err(ErrorInJwt::JwksFetchFailed)
+        ));
+    }
+
+    #[cfg(feature = "http")]
+    #[test]
+    fn test_verify_with_jwks_url_requires_https() {
+        let token = create_test_token("test_kid");
+
+        assert!(matches!(
+            validator::fetch::verify_with_jwks_url(&token, "http://127.0.0.1/jwks.json"),
+            Err(ErrorInJwt::InsecureJwksUrl)
+        ));
+    }
+
+    #[test]
+    fn test_verify_jwt_malformed_key_components() {
+        let kid = "test_kid";
+        let jwks = JwkSet {
+            keys: vec![create_test_jwk(kid, "not*base64", "AQAB")],
+        };
+        let token = create_test_token(kid);
+
+        assert!(matches!(
+            verify_jwt(&token, &jwks),
+            Err(ErrorInJwt::NotPossibleToGetDecodeKey)
+        ));
+        assert!(matches!(
+            verify_jwt_strict(
+                &token,
+                &jwks,
+                "test_issuer",
+                &["test_audience"],
+                &SystemClock
+            ),
+            Err(ErrorInJwt::NotPossibleToGetDecodeKey)
+        ));
+    }
+
+    #[test]
+    fn test_split_jws() {
+        assert_eq!(split_jws("aaa.bbb.ccc").unwrap(), ("aaa", "bbb", "ccc"));
+
+        let malformed_tokens = ["a..c", ".b.c", "a.b.", "a.b", "a", "", "a.b.c.d", "..."];
+        for token in malformed_tokens {
+            assert_eq!(split_jws(token), Err(ErrorInJwt::InvalidToken));
+        }
+    }
+
+    #[test]
+    fn test_verify_jwt_report_expires_at() {
+        let kid = "test_kid";
+        let jwks = create_test_key_set(kid);
+        let token = create_test_token_with_claims(kid, &create_test_claims(1_000));
+        let options = VerifyOptions::default();
+
+        let report = verify_jwt_report(&token, &jwks, &options, &FixedClock(900));
+        assert!(report.result.is_ok());
+        assert_eq!(report.expires_at, Some(1_000));
+        assert_eq!(report.remaining_secs, Some(100));
+
+        let report = verify_jwt_report(&token, &jwks, &options, &FixedClock(1_180));
+        assert!(matches!(report.result, Err(ErrorInJwt::TokenExpired)));
+        assert_eq!(report.expires_at, Some(1_000));
+        assert_eq!(report.remaining_secs, None);
+
+        let claims = get_claims(&token).unwrap();
+        assert_eq!(remaining_validity(&claims, 1_180), Some(0));
+        assert_eq!(
+            remaining_validity(&create_test_claims(2_000), 1_900),
+            Some(100)
+        );
+    }
+
+    #[test]
+    fn test_verify_jwt_report_unverified_token() {
+        let token = create_test_token_with_claims("test_kid", &create_test_claims(1_000));
+        let jwks = create_test_key_set("other_kid");
+
+        let report = verify_jwt_report(&token, &jwks, &VerifyOptions::default(), &FixedClock(900));
+        assert!(matches!(report.result, Err(ErrorInJwt::NoJwkForKid)));
+        assert_eq!(report.expires_at, None);
+    }
+
+    #[test]
+    fn test_verify_jwt_strict_any_audience() {
+        let kid = "test_kid";
+        let jwks = create_test_key_set(kid);
+        let token = create_test_token(kid);
+
+        let accepted = ["resource_server", "test_audience", "resource_alias"];
+        assert!(verify_jwt_strict(&token, &jwks, "test_issuer", &accepted, &SystemClock).is_ok());
+    }
+
+    #[test]
+    fn test_verify_jwt_strict_array_audience() {
+        let kid = "test_kid";
+        let jwks = create_test_key_set(kid);
+        let token = create_test_token_with_claims(
+            kid,
+            &serde_json::json!({
+                "iss": "test_issuer",
+                "aud": ["other_audience", "test_audience"],
+                "sub": "user123",
+                "exp": u64::MAX,
+            }),
+        );
+
+        let token_data = verify_jwt_strict(
+            &token,
+            &jwks,
+            "test_issuer",
+            &["test_audience"],
+            &SystemClock,
+        )
+        .unwrap();
+        assert_eq!(token_data.claims.aud, ["other_audience", "test_audience"]);
+        assert_eq!(
+            get_claims(&create_test_token(kid)).unwrap().aud,
+            ["test_audience"]
+        );
+        assert!(matches!(
+            verify_jwt_strict(
+                &token,
+                &jwks,
+                "test_issuer",
+                &["resource_server"],
+                &SystemClock
+            ),
+            Err(ErrorInJwt::InvalidAudience)
+        ));
+    }
+
+    #[test]
+    fn test_jwk_algorithm_must_match_parameters() {
+        let kid = "test_kid";
+        let mut jwk = create_test_key_jwk(kid);
+        jwk.common.key_algorithm = Some(KeyAlgorithm::ES256);
+        let jwks = JwkSet { keys: vec![jwk] };
+        let token = create_test_token(kid);
+
+        assert!(matches!(
+            validate_jwks(&jwks, MIN_RSA_MODULUS_BITS),
+            Err(ErrorInJwt::InvalidJwk)
+        ));
+        assert!(matches!(
+            verify_jwt(&token, &jwks),
+            Err(ErrorInJwt::InvalidJwk)
+        ));
+    }
+
+    #[test]
+    fn test_jwk_type_must_match_parameters() {
+        let kid = "test_kid";
+        let mut jwk = serde_json::to_value(create_test_key_jwk(kid)).unwrap();
+        jwk["kty"] = "EC".into();
+        let jwks_bytes = serde_json::to_vec(&serde_json::json!({ "keys": [jwk] })).unwrap();
+        let token = create_test_token(kid);
+
+        // A known `kty` with the members of another is malformed, not skipped
+        assert!(serde_json::from_slice::<JwkSet>(&jwks_bytes).is_err());
+        assert!(matches!(
+            parse_jwks_lenient(&jwks_bytes),
+            Err(ErrorInJwt::InvalidJwk)
+        ));
+        assert!(matches!(
+            verify_jwt_from_bytes(&token, &jwks_bytes),
+            Err(ErrorInJwt::InvalidJwk)
+        ));
+    }
+
+    #[test]
+    fn test_verify_jwt_report_max_age() {
+        let kid = "test_kid";
+        let jwks = create_test_key_set(kid);
+        let mut claims = create_test_claims(u64::MAX);
+        claims.iat = Some(1_000);
+        let token = create_test_token_with_claims(kid, &claims);
+        let now = 10_000;
+
+        let report = verify_jwt_report(&token, &jwks, &VerifyOptions::default(), &FixedClock(now));
+        assert!(report.result.is_ok());
+
+        let options = VerifyOptions {
+            max_age_secs: Some(300),
+            ..Default::default()
+        };
+        let report = verify_jwt_report(&token, &jwks, &options, &FixedClock(now));
+        assert!(matches!(report.result, Err(ErrorInJwt::TokenTooOld)));
+
+        let options = VerifyOptions {
+            max_age_secs: Some(300),
+            leeway_secs: 9_000,
+            ..Default::default()
+        };
+        let report = verify_jwt_report(&token, &jwks, &options, &FixedClock(now));
+        assert!(report.result.is_ok());
+    }
+
+    #[test]
+    fn test_verify_jwt_report_max_age_requires_iat() {
+        let kid = "test_kid";
+        let jwks = create_test_key_set(kid);
+        let token = create_test_token(kid);
+        let options = VerifyOptions {
+            max_age_secs: Some(300),
+            ..Default::default()
+        };
+
+        let report = verify_jwt_report(&token, &jwks, &options, &FixedClock(10_000));
+        assert!(matches!(report.result, Err(ErrorInJwt::MissingIssuedAt)));
+    }
+
+    #[test]
+    fn test_verify_jwt_report_expiry_boundary_with_fixed_clock() {
+        let kid = "test_kid";
+        let jwks = create_test_key_set(kid);
+        let token = create_test_token_with_claims(kid, &create_test_claims(1_000));
+        let options = VerifyOptions::default();
+
+        let report = verify_jwt_report(&token, &jwks, &options, &FixedClock(999));
+        assert!(report.result.is_ok());
+        let report = verify_jwt_report(&token, &jwks, &options, &FixedClock(1_000));
+        assert!(matches!(report.result, Err(ErrorInJwt::TokenExpired)));
+
+        let token = create_test_token(kid);
+        let report = verify_jwt_report(&token, &jwks, &options, &SystemClock);
+        assert!(report.result.is_ok());
+    }
+
+    #[test]
+    fn test_verify_jwt_staged_reports_failed_stage() {
+        let kid = "test_kid";
+        let jwks = create_test_key_set(kid);
+        let token = create_test_token_with_claims(kid, &create_test_claims(1_000));
+        let options = VerifyOptions::default();
+
+        assert!(verify_jwt_staged(&token, &jwks, &options, &FixedClock(900)).is_ok());
+        assert_eq!(
+            verify_jwt_staged(&token, &jwks, &options, &FixedClock(1_000)).unwrap_err(),
+            VerifyError {
+                stage: VerifyStage::Claims,
+                detail: ErrorInJwt::TokenExpired,
+            }
+        );
+        assert_eq!(
+            verify_jwt_staged("a.b", &jwks, &options, &FixedClock(900)).unwrap_err(),
+            VerifyError::new(VerifyStage::Header, ErrorInJwt::InvalidToken)
+        );
+
+        let other_jwks = create_test_key_set("other_kid");
+        assert_eq!(
+            verify_jwt_staged(&token, &other_jwks, &options, &FixedClock(900)).unwrap_err(),
+            VerifyError::new(VerifyStage::KeyLookup, ErrorInJwt::NoJwkForKid)
+        );
+    }
+
+    #[test]
+    fn test_verify_jwt_kid_less_single_key() {
+        let mut jwk = create_test_key_jwk("unused");
+        jwk.common.key_id = None;
+        jwk.common.key_algorithm = None;
+        let jwks = JwkSet { keys: vec![jwk] };
+
+        let header = Header::new(Algorithm::RS512);
+        let token = encode(
+            &header,
+            &create_test_claims(u64::MAX),
+            &EncodingKey::from_rsa_pem(RSA_PEM.as_bytes()).unwrap(),
+        )
+        .unwrap();
+
+        assert!(matches!(verify_jwt(&token, &jwks), Ok(true)));
+        assert!(
+            verify_jwt_strict(
+                &token,
+                &jwks,
+                "test_issuer",
+                &["test_audience"],
+                &SystemClock
+            )
+            .is_ok()
+        );
+    }
+
+    #[test]
+    fn test_verify_jwt_kid_less_requires_kid_for_many_keys() {
+        let jwks = JwkSet {
+            keys: vec![create_test_key_jwk("kid_1"), create_test_key_jwk("kid_2")],
+        };
+        let token = encode(
+            &Header::new(Algorithm::RS256),
+            &create_test_claims(u64::MAX),
+            &EncodingKey::from_rsa_pem(RSA_PEM.as_bytes()).unwrap(),
+        )
+        .unwrap();
+
+        assert!(matches!(
+            verify_jwt(&token, &jwks),
+            Err(ErrorInJwt::MissingKid)
+        ));
+    }
+
+    #[test]
+    fn test_verify_jwt_algorithm_must_match_key() {
+        // The key declares RS256, the token claims RS512
+        let kid = "test_kid";
+        let jwks = create_test_key_set(kid);
+        let mut header = Header::new(Algorithm::RS512);
+        header.kid = Some(kid.to_string());
+        let token = encode(
+            &header,
+            &create_test_claims(u64::MAX),
+            &EncodingKey::from_rsa_pem(RSA_PEM.as_bytes()).unwrap(),
+        )
+        .unwrap();
+
+        assert!(matches!(
+            verify_jwt(&token, &jwks),
+            Err(ErrorInJwt::AlgorithmNotSupported)
+        ));
+    }
+
+    #[test]
+    fn test_verify_jwt_with_key() {
+        let token = create_test_token("test_kid");
+        let private_key = RsaPrivateKey::from_pkcs8_pem(RSA_PEM).unwrap();
+        let key = DecodingKey::from_rsa_raw_components(
+            &private_key.n().to_bytes_be(),
+            &private_key.e().to_bytes_be(),
+        );
+
+        assert!(matches!(
+            verify_jwt_with_key(&token, &key, Algorithm::RS256),
+            Ok(true)
+        ));
+        assert!(matches!(
+            verify_jwt_with_key(&token, &key, Algorithm::RS512),
+            Err(ErrorInJwt::AlgorithmNotSupported)
+        ));
+        assert!(matches!(
+            verify_jwt_with_key(&token, &key, Algorithm::HS256),
+            Err(ErrorInJwt::AlgorithmNotSupported)
+        ));
+
+        let other_key = DecodingKey::from_rsa_raw_components(&[0xff; 256], &[1, 0, 1]);
+        assert!(matches!(
+            verify_jwt_with_key(&token, &other_key, Algorithm::RS256),
+            Ok(false)
+        ));
+    }
+
+    #[test]
+    fn test_oidc_half_hash() {
+        // Example from OpenID Connect Core 1.0, appendix A.3
+        assert_eq!(
+            oidc_half_hash(
+                "jHkWEdUXMU1BwAsC4vtUsZwnNvTIxEl0z9K3vx5KF0Y",
+                Algorithm::RS256
+            ),
+            "77QmUPtjPfzWtF2AnpK9RQ"
+        );
+    }
+
+    #[test]
+    fn test_verify_jwt_with_hashes() {
+        let kid = "test_kid";
+        let jwks = create_test_key_set(kid);
+        let access_token = "an_access_token";
+        let code = "an_authorization_code";
+        let mut claims = create_test_claims(u64::MAX);
+        claims.at_hash = Some(oidc_half_hash(access_token, Algorithm::RS256));
+        claims.c_hash = Some(oidc_half_hash(code, Algorithm::RS256));
+        let token = create_test_token_with_claims(kid, &claims);
+        let options = VerifyOptions::default();
+
+        assert!(
+            verify_jwt_with_hashes(&token, &jwks, &options, None, None, &FixedClock(900)).is_ok()
+        );
+        assert!(
+            verify_jwt_with_hashes(
+                &token,
+                &jwks,
+                &options,
+                Some(access_token),
+                Some(code),
+                &FixedClock(900)
+            )
+            .is_ok()
+        );
+        assert!(matches!(
+            verify_jwt_with_hashes(
+                &token,
+                &jwks,
+                &options,
+                Some("another_token"),
+                None,
+                &FixedClock(900)
+            ),
+            Err(ErrorInJwt::HashMismatch)
+        ));
+        assert!(matches!(
+            verify_jwt_with_hashes(
+                &token,
+                &jwks,
+                &options,
+                None,
+                Some("another_code"),
+                &FixedClock(900)
+            ),
+            Err(ErrorInJwt::HashMismatch)
+        ));
+
+        // Matching hashes don't save an expired token
+        claims.exp = Some(1_000);
+        let expired = create_test_token_with_claims(kid, &claims);
+        assert!(matches!(
+            verify_jwt_with_hashes(
+                &expired,
+                &jwks,
+                &options,
+                Some(access_token),
+                Some(code),
+                &FixedClock(1_000)
+            ),
+            Err(ErrorInJwt::TokenExpired)
+        ));
+    }
+
+    #[test]
+    fn test_verify_jwt_rejects_algorithm_confusion() {
+        // An RSA key can't stand in for an HMAC secret
+        let kid = "test_kid";
+        let mut jwk = create_test_key_jwk(kid);
+        jwk.common.key_algorithm = None;
+        let jwks = JwkSet { keys: vec![jwk] };
+        let mut header = Header::new(Algorithm::HS256);
+        header.kid = Some(kid.to_string());
+        let token = encode(
+            &header,
+            &create_test_claims(u64::MAX),
+            &EncodingKey::from_secret(b"the public modulus, maybe"),
+        )
+        .unwrap();
+
+        assert!(matches!(
+            verify_jwt(&token, &jwks),
+            Err(ErrorInJwt::AlgorithmNotSupported)
+        ));
+        assert!(matches!(
+            verify_jwt_strict(
+                &token,
+                &jwks,
+                "test_issuer",
+                &["test_audience"],
+                &SystemClock
+            ),
+            Err(ErrorInJwt::AlgorithmNotSupported)
+        ));
+    }
+
+    #[test]
+    fn test_verify_jwt_ed25519() {
+        let kid = "ed_kid";
+        let jwks = JwkSet {
+            keys: vec![create_test_ed25519_jwk(kid)],
+        };
+        let mut header = Header::new(Algorithm::EdDSA);
+        header.kid = Some(kid.to_string());
+        let token = encode(
+            &header,
+            &create_test_claims(u64::MAX),
+            &EncodingKey::from_ed_pem(ED25519_PEM.as_bytes()).unwrap(),
+        )
+        .unwrap();
+
+        assert!(matches!(verify_jwt(&token, &jwks), Ok(true)));
+
+        // ...and an Ed25519 key doesn't verify RSA tokens
+        let rsa_token = create_test_token(kid);
+        assert!(matches!(
+            verify_jwt(&rsa_token, &jwks),
+            Err(ErrorInJwt::AlgorithmNotSupported)
+        ));
+    }
+
+    #[test]
+    fn test_verify_jwt_es256() {
+        let kid = "ec_kid";
+        let jwks = JwkSet {
+            keys: vec![create_test_ec_jwk(kid)],
+        };
+
+        assert_eq!(verify_jwt(&create_test_es256_token(kid), &jwks), Ok(true));
+        assert!(matches!(
+            verify_jwt(&create_test_token(kid), &jwks),
+            Err(ErrorInJwt::AlgorithmNotSupported)
+        ));
+    }
+
+    #[test]
+    fn test_verify_jwt_provider_shaped_jwks() {
+        // Serialized the way providers publish them, with `use` and mixed key types
+        let mut rsa_jwk = serde_json::to_value(create_test_key_jwk("rsa_kid")).unwrap();
+        rsa_jwk["use"] = "sig".into();
+        let mut ec_jwk = serde_json::to_value(create_test_ec_jwk("ec_kid")).unwrap();
+        ec_jwk["use"] = "sig".into();
+        let jwks_bytes =
+            serde_json::to_vec(&serde_json::json!({ "keys": [rsa_jwk, ec_jwk] })).unwrap();
+
+        let jwks = parse_jwks_lenient(&jwks_bytes).unwrap();
+        assert_eq!(jwks.keys.len(), 2);
+        assert!(validate_jwks(&jwks, MIN_RSA_MODULUS_BITS).is_ok());
+        assert_eq!(
+            verify_jwt_from_bytes(&create_test_token("rsa_kid"), &jwks_bytes),
+            Ok(true)
+        );
+        assert_eq!(
+            verify_jwt_from_bytes(&create_test_es256_token("ec_kid"), &jwks_bytes),
+            Ok(true)
+        );
+    }
+
+    #[test]
+    fn test_verify_jwt_json_custom_claims() {
+        let kid = "test_kid";
+        let jwks = create_test_key_set(kid);
+        let mut header = Header::new(Algorithm::RS256);
+        header.kid = Some(kid.to_string());
+        let payload = serde_json::json!({
+            "iss": "test_issuer",
+            "aud": ["other_audience", "test_audience"],
+            "sub": "user123",
+            "exp": 1_000,
+            "email": "user@example.com",
+        });
+        let token = encode(
+            &header,
+            &payload,
+            &EncodingKey::from_rsa_pem(RSA_PEM.as_bytes()).unwrap(),
+        )
+        .unwrap();
+
+        let claims = verify_jwt_json(
+            &token,
+            &jwks,
+            "test_issuer",
+            &["test_audience"],
+            &FixedClock(900),
+        )
+        .unwrap();
+        assert_eq!(claims["email"], "user@example.com");
+
+        assert!(matches!(
+            verify_jwt_json(
+                &token,
+                &jwks,
+                "other_issuer",
+                &["test_audience"],
+                &FixedClock(900)
+            ),
+            Err(ErrorInJwt::InvalidIssuer)
+        ));
+        assert!(matches!(
+            verify_jwt_json(
+                &token,
+                &jwks,
+                "test_issuer",
+                &["resource_server"],
+                &FixedClock(900)
+            ),
+            Err(ErrorInJwt::InvalidAudience)
+        ));
+        assert!(matches!(
+            verify_jwt_json(
+                &token,
+                &jwks,
+                "test_issuer",
+                &["test_audience"],
+                &FixedClock(1_000)
+            ),
+            Err(ErrorInJwt::TokenExpired)
+        ));
+    }
+
+    #[cfg(feature = "substrate")]
+    fn sign_substrate_jws(alg: &str, sign: impl Fn(&[u8]) -> Vec<u8>) -> String {
+        let header = URL_SAFE_NO_PAD.encode(format!(r#"{{"alg":"{alg}","typ":"JWT"}}"#));
+        let payload = URL_SAFE_NO_PAD.encode(r#"{"sub":"5GrwvaEF","stmt":"hello"}"#);
+        let signing_input = format!("{header}.{payload}");
+        let signature = URL_SAFE_NO_PAD.encode(sign(signing_input.as_bytes()));
+        format!("{signing_input}.{signature}")
+    }
+
+    #[cfg(feature = "substrate")]
+    #[test]
+    fn test_verify_substrate_jws_ed25519() {
+        use sp_core::{Pair, ed25519};
+        use validator::substrate::{SubstratePublic, verify_substrate_jws};
+
+        let pair = ed25519::Pair::from_seed(&[7u8; 32]);
+        let public = SubstratePublic::Ed25519(pair.public().0);
+        let token = sign_substrate_jws("EdDSA", |msg| pair.sign(msg).0.to_vec());
+        assert_eq!(verify_substrate_jws(&token, &public), Ok(true));
+
+        let other = ed25519::Pair::from_seed(&[8u8; 32]);
+        let forged = sign_substrate_jws("EdDSA", |msg| other.sign(msg).0.to_vec());
+        assert_eq!(verify_substrate_jws(&forged, &public), Ok(false));
+
+        let wrong_alg = sign_substrate_jws("Sr25519", |msg| pair.sign(msg).0.to_vec());
+        assert_eq!(
+            verify_substrate_jws(&wrong_alg, &public),
+            Err(ErrorInJwt::AlgorithmNotSupported)
+        );
+    }
+
+    #[cfg(feature = "substrate")]
+    #[test]
+    fn test_verify_substrate_jws_sr25519() {
+        use sp_core::{Pair, sr25519};
+        use validator::substrate::{SubstratePublic, verify_substrate_jws};
+
+        let pair = sr25519::Pair::from_seed(&[7u8; 32]);
+        let public = SubstratePublic::Sr25519(pair.public().0);
+        let token = sign_substrate_jws("Sr25519", |msg| pair.sign(msg).0.to_vec());
+        assert_eq!(verify_substrate_jws(&token, &public), Ok(true));
+
+        let other = sr25519::Pair::from_seed(&[8u8; 32]);
+        let forged = sign_substrate_jws("Sr25519", |msg| other.sign(msg).0.to_vec());
+        assert_eq!(verify_substrate_jws(&forged, &public), Ok(false));
+    }
+
+    #[cfg(feature = "substrate")]
+    #[test]
+    fn test_verify_substrate_jws_ecdsa() {
+        use sha2::{Digest, Sha256};
+        use sp_core::{Pair, ecdsa};
+        use validator::substrate::{SubstratePublic, verify_substrate_jws};
+
+        let pair = ecdsa::Pair::from_seed(&[7u8; 32]);
+        let public = SubstratePublic::Ecdsa(pair.public().0);
+        let token = sign_substrate_jws("ES256K", |msg| {
+            pair.sign_prehashed(&Sha256::digest(msg).into()).0[..64].to_vec()
+        });
+        assert_eq!(verify_substrate_jws(&token, &public), Ok(true));
+
+        let other = ecdsa::Pair::from_seed(&[8u8; 32]);
+        let forged = sign_substrate_jws("ES256K", |msg| {
+            other.sign_prehashed(&Sha256::digest(msg).into()).0[..64].to_vec()
+        });
+        assert_eq!(verify_substrate_jws(&forged, &public), Ok(false));
+
+        // sp_core's own 65-byte `r || s || v` encoding isn't a JWS signature
+        let recoverable = sign_substrate_jws("ES256K", |msg| {
+            pair.sign_prehashed(&Sha256::digest(msg).into()).0.to_vec()
+        });
+        assert_eq!(
+            verify_substrate_jws(&recoverable, &public),
+            Err(ErrorInJwt::InvalidJwt)
+        );
+    }
 }